@@ -0,0 +1,125 @@
+//! 多标的批量计算：每个标的一个独立的 `KLineList`，分摊到若干工作线程上并行跑，适合全市场选股
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::chan_config::ChanConfig;
+use crate::common::chan_exception::{ChanException, ChanResult, ErrCode};
+use crate::common::enums::KLineType;
+use crate::common::messages::{tr, MsgKey};
+use crate::kline::kline_list::KLineList;
+
+/// 单个标的的计算结果，出错或任务 panic 只影响该标的
+#[derive(Debug)]
+pub struct SymbolResult<T> {
+    pub code: String,
+    pub result: ChanResult<T>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BatchProcessor {
+    kl_type: Option<KLineType>,
+    config: ChanConfig,
+    threads: usize,
+}
+
+impl BatchProcessor {
+    /// 线程数默认取可用的CPU核数
+    pub fn new(kl_type: Option<KLineType>, config: ChanConfig) -> Self {
+        Self {
+            kl_type,
+            config,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+
+    /// 工作线程数，至少为1
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// 对每个标的新建一个空的 `KLineList`，交给 `job` 灌入数据并算出结果；
+    /// 各标的谁先空闲谁领取，返回顺序与 `codes` 一致
+    pub fn run<S, T, F>(&self, codes: &[S], job: F) -> Vec<SymbolResult<T>>
+    where
+        S: AsRef<str> + Sync,
+        T: Send,
+        F: Fn(&str, &mut KLineList) -> ChanResult<T> + Sync,
+    {
+        let next = AtomicUsize::new(0);
+        let results: Mutex<Vec<Option<ChanResult<T>>>> =
+            Mutex::new((0..codes.len()).map(|_| None).collect());
+        thread::scope(|s| {
+            for _ in 0..self.threads.min(codes.len()) {
+                s.spawn(|| loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(code) = codes.get(i).map(AsRef::as_ref) else {
+                        break;
+                    };
+                    let mut kl_list = KLineList::with_config(self.kl_type, self.config.clone());
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| job(code, &mut kl_list)))
+                        .unwrap_or_else(|_| {
+                            Err(ChanException::new(
+                                tr(MsgKey::BatchJobPanicked, &[&code]),
+                                ErrCode::CommonError,
+                            ))
+                        });
+                    results.lock().expect("no panic while holding the lock")[i] = Some(result);
+                });
+            }
+        });
+        let results = results.into_inner().expect("workers have finished");
+        codes
+            .iter()
+            .zip(results)
+            .map(|(code, result)| SymbolResult {
+                code: code.as_ref().to_string(),
+                result: result.expect("every code is processed"),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::ctime::CTime;
+    use crate::testkit::MarketGen;
+
+    #[test]
+    fn test_run() {
+        let codes: Vec<String> = (0..20).map(|i| format!("sz.{:06}", i)).collect();
+        let processor =
+            BatchProcessor::new(Some(KLineType::KDay), ChanConfig::default()).threads(4);
+        let results = processor.run(&codes, |code, kl_list| {
+            let waves: usize = code[3..].parse().unwrap();
+            if waves == 7 {
+                panic!("bad data");
+            }
+            let series = MarketGen::new(CTime::new(2024, 1, 1, 0, 0, 0, false), 10.0)
+                .trend(waves, 2.0, 1.0, 4)
+                .build()?;
+            for klu in series.klus {
+                kl_list.add_single_klu(klu)?;
+            }
+            Ok(kl_list.fx_list().len())
+        });
+
+        assert_eq!(results.len(), codes.len());
+        for (i, item) in results.iter().enumerate() {
+            assert_eq!(item.code, codes[i]);
+            match i {
+                7 => assert_eq!(
+                    item.result.as_ref().unwrap_err().errcode,
+                    ErrCode::CommonError
+                ),
+                // 每浪一顶一底，最后一浪回调出的底分型还缺右侧KLC
+                0 => assert_eq!(*item.result.as_ref().unwrap(), 0),
+                _ => assert_eq!(*item.result.as_ref().unwrap(), 2 * i - 1),
+            }
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use crate::common::chan_exception::{ChanException, ChanResult, ErrCode};
+use crate::common::enums::FxCheckMethod;
+use crate::common::messages::{tr, MsgKey};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BiConfig {
+    pub bi_algo: String,
+    pub is_strict: bool,
+    pub bi_fx_check: FxCheckMethod,
+    pub gap_as_kl: bool,
+    pub bi_end_is_peak: bool,
+    pub bi_allow_sub_peak: bool,
+}
+
+impl BiConfig {
+    pub fn new(
+        bi_algo: &str,
+        is_strict: bool,
+        bi_fx_check: &str,
+        gap_as_kl: bool,
+        bi_end_is_peak: bool,
+        bi_allow_sub_peak: bool,
+    ) -> ChanResult<Self> {
+        Self {
+            bi_algo: bi_algo.to_string(),
+            is_strict,
+            bi_fx_check: bi_fx_check.parse()?,
+            gap_as_kl,
+            bi_end_is_peak,
+            bi_allow_sub_peak,
+        }
+        .validate()
+    }
+
+    pub fn builder() -> BiConfigBuilder {
+        BiConfigBuilder::new(Self::default())
+    }
+
+    /// `new` 和 `BiConfigBuilder::build` 共用的取值检查
+    fn validate(self) -> ChanResult<Self> {
+        if self.bi_algo != "normal" && self.bi_algo != "fx" {
+            return Err(ChanException::new(
+                tr(MsgKey::UnknownBiAlgo, &[&self.bi_algo]),
+                ErrCode::ParaError,
+            ));
+        }
+        Ok(self)
+    }
+}
+
+/// 逐项设置 `BiConfig`，取值在 `build` 时统一检查
+#[derive(Debug, Clone)]
+pub struct BiConfigBuilder {
+    conf: BiConfig,
+}
+
+impl BiConfigBuilder {
+    pub fn new(conf: BiConfig) -> Self {
+        Self { conf }
+    }
+
+    pub fn bi_algo(mut self, bi_algo: &str) -> Self {
+        self.conf.bi_algo = bi_algo.to_string();
+        self
+    }
+
+    pub fn is_strict(mut self, is_strict: bool) -> Self {
+        self.conf.is_strict = is_strict;
+        self
+    }
+
+    pub fn fx_check(mut self, bi_fx_check: FxCheckMethod) -> Self {
+        self.conf.bi_fx_check = bi_fx_check;
+        self
+    }
+
+    pub fn gap_as_kl(mut self, gap_as_kl: bool) -> Self {
+        self.conf.gap_as_kl = gap_as_kl;
+        self
+    }
+
+    pub fn end_is_peak(mut self, bi_end_is_peak: bool) -> Self {
+        self.conf.bi_end_is_peak = bi_end_is_peak;
+        self
+    }
+
+    pub fn allow_sub_peak(mut self, bi_allow_sub_peak: bool) -> Self {
+        self.conf.bi_allow_sub_peak = bi_allow_sub_peak;
+        self
+    }
+
+    pub fn build(self) -> ChanResult<BiConfig> {
+        self.conf.validate()
+    }
+}
+
+/// 同 chan.py 的默认笔配置
+impl Default for BiConfig {
+    fn default() -> Self {
+        Self {
+            bi_algo: "normal".to_string(),
+            is_strict: true,
+            bi_fx_check: FxCheckMethod::Strict,
+            gap_as_kl: false,
+            bi_end_is_peak: true,
+            bi_allow_sub_peak: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chan_config::ChanConfig;
+
+    #[test]
+    fn test_parse_fx_check() {
+        let conf = BiConfig::new("normal", true, "strict", false, true, true).unwrap();
+        assert_eq!(conf.bi_fx_check, FxCheckMethod::Strict);
+        let err = BiConfig::new("normal", true, "xxx", false, true, true).unwrap_err();
+        assert_eq!(err.errcode, ErrCode::ParaError);
+        let err = BiConfig::new("xxx", true, "strict", false, true, true).unwrap_err();
+        assert_eq!(err.errcode, ErrCode::ParaError);
+    }
+
+    #[test]
+    fn test_builder() {
+        let conf = BiConfig::builder()
+            .fx_check(FxCheckMethod::Loss)
+            .gap_as_kl(false)
+            .build()
+            .unwrap();
+        assert_eq!(conf.bi_fx_check, FxCheckMethod::Loss);
+        assert!(!conf.gap_as_kl);
+        assert!(conf.is_strict);
+
+        let err = BiConfig::builder().bi_algo("xxx").build().unwrap_err();
+        assert_eq!(err.errcode, ErrCode::ParaError);
+        // 与 `ChanConfig` 里的笔配置起点相同
+        assert_eq!(
+            BiConfig::builder().build().unwrap(),
+            ChanConfig::builder().bi(|b| b).build().unwrap().bi_conf
+        );
+    }
+}
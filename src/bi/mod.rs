@@ -0,0 +1 @@
+pub mod bi_config;
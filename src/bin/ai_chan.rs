@@ -0,0 +1,194 @@
+//! 命令行分析工具：读入csv，计算KLC和分形，导出 fx.csv / fx.json / halted.csv 并打印统计信息
+//!
+//! ai_chan <csv> [--kl-type 15] [--config conf.txt] [--out dir] [--begin 2024-01-01] [--end 2024-12-31] [--no-header]
+//!
+//! csv 默认第一行是表头，没有表头时加 `--no-header`；
+//! 配置文件每行一个 `key = value`，`#` 开头为注释，key 见 `ChanConfigBuilder::set`
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use chan_ai::chan_config::ChanConfig;
+use chan_ai::common::enums::KLineType;
+use chan_ai::data_api::csv_api::load_csv;
+use chan_ai::kline::kline_list::KLineList;
+
+struct Args {
+    csv: PathBuf,
+    kl_type: KLineType,
+    config: Option<PathBuf>,
+    out: PathBuf,
+    begin: Option<String>,
+    end: Option<String>,
+    headers_exist: bool,
+}
+
+/// `argv` 不含程序名
+fn parse_args(argv: impl IntoIterator<Item = String>) -> Result<Args, String> {
+    let mut it = argv.into_iter();
+    let mut args = Args {
+        csv: PathBuf::new(),
+        kl_type: KLineType::KDay,
+        config: None,
+        out: PathBuf::from("."),
+        begin: None,
+        end: None,
+        headers_exist: true,
+    };
+    let mut csv = None;
+    while let Some(arg) = it.next() {
+        let mut value = || it.next().ok_or(format!("missing value for {arg}"));
+        match arg.as_str() {
+            "--kl-type" => {
+                let v = value()?;
+                args.kl_type = v
+                    .parse()
+                    .ok()
+                    .and_then(KLineType::from_value)
+                    .ok_or(format!("unknown kl type: {v}"))?;
+            }
+            "--config" => args.config = Some(value()?.into()),
+            "--out" => args.out = value()?.into(),
+            "--begin" => args.begin = Some(value()?),
+            "--end" => args.end = Some(value()?),
+            "--no-header" => args.headers_exist = false,
+            _ if csv.is_none() && !arg.starts_with("--") => csv = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unknown argument: {arg}")),
+        }
+    }
+    args.csv = csv.ok_or("usage: ai_chan <csv> [--kl-type N] [--config FILE] [--out DIR] [--begin DATE] [--end DATE] [--no-header]")?;
+    Ok(args)
+}
+
+fn apply_config(text: &str) -> Result<ChanConfig, String> {
+    let mut builder = ChanConfig::builder();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, v) = line
+            .split_once('=')
+            .ok_or(format!("invalid config line: {line}"))?;
+        builder = builder.set(key.trim(), v.trim());
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+fn run() -> Result<(), String> {
+    let args = parse_args(std::env::args().skip(1))?;
+    let config = match &args.config {
+        Some(path) => apply_config(
+            &std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?,
+        )?,
+        None => ChanConfig::default(),
+    };
+    let klus = load_csv(
+        &args.csv,
+        Some(args.kl_type),
+        args.headers_exist,
+        args.begin.as_deref(),
+        args.end.as_deref(),
+    )
+    .map_err(|e| e.to_string())?;
+    let mut kl_list = KLineList::with_config(Some(args.kl_type), config);
+    for klu in klus {
+        kl_list.add_single_klu(klu).map_err(|e| e.to_string())?;
+    }
+
+    std::fs::create_dir_all(&args.out).map_err(|e| e.to_string())?;
+    for (name, content) in [
+        (
+            "fx.csv",
+            kl_list.fx_list().to_csv_with(kl_list.symbol_info()),
+        ),
+        (
+            "fx.json",
+            kl_list.fx_list().to_json_with(kl_list.symbol_info()),
+        ),
+        ("halted.csv", halted_csv(&kl_list)),
+    ] {
+        let path = args.out.join(name);
+        std::fs::write(&path, content).map_err(|e| format!("{}: {e}", path.display()))?;
+    }
+    let summary = kl_list.summary();
+    if let Some((begin, end)) = summary.time_range {
+        println!("time: {begin} ~ {end}");
+    }
+    println!(
+        "klu: {}, klc: {}, invalid bar: {}, halted: {}",
+        summary.klu_cnt, summary.klc_cnt, summary.invalid_bar_cnt, summary.halted_klu_cnt
+    );
+    println!(
+        "fx: top={}, bottom={}, unsure={}",
+        summary.top_fx_cnt, summary.bottom_fx_cnt, summary.unsure_fx_cnt
+    );
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn halted_csv(kl_list: &KLineList) -> String {
+    let mut out = String::from("begin,end\n");
+    for (begin, end) in kl_list.halted_ranges() {
+        out.push_str(&format!("{begin},{end}\n"));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chan_ai::common::enums::FxCheckMethod;
+
+    fn args(argv: &[&str]) -> Result<Args, String> {
+        parse_args(argv.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let parsed = args(&[
+            "a.csv",
+            "--kl-type",
+            "8",
+            "--no-header",
+            "--end",
+            "2024-12-31",
+        ])
+        .unwrap();
+        assert_eq!(parsed.csv, PathBuf::from("a.csv"));
+        assert_eq!(parsed.kl_type, KLineType::K1M);
+        assert!(!parsed.headers_exist);
+        assert_eq!(parsed.end.as_deref(), Some("2024-12-31"));
+        assert_eq!(parsed.out, PathBuf::from("."));
+
+        let parsed = args(&["a.csv"]).unwrap();
+        assert!(parsed.headers_exist);
+        assert_eq!(parsed.kl_type, KLineType::KDay);
+
+        assert!(args(&[]).is_err());
+        assert!(args(&["a.csv", "--kl-type", "99"]).is_err());
+        assert!(args(&["a.csv", "--out"]).is_err());
+        assert!(args(&["a.csv", "b.csv"]).is_err());
+    }
+
+    #[test]
+    fn test_apply_config() {
+        let conf = apply_config("# comment\n\nbi_fx_check = half\ncombine_dir=close\n").unwrap();
+        assert_eq!(conf.bi_conf.bi_fx_check, FxCheckMethod::Half);
+        assert_ne!(conf.combine_dir, ChanConfig::default().combine_dir);
+
+        assert!(apply_config("bi_fx_check").is_err());
+        assert!(apply_config("bi_fx_check = xxx")
+            .unwrap_err()
+            .contains("bi_fx_check"));
+        assert!(apply_config("xxx = 1").is_err());
+    }
+}
@@ -0,0 +1,357 @@
+use crate::bi::bi_config::{BiConfig, BiConfigBuilder};
+use crate::common::chan_exception::{ChanException, ChanResult, ErrCode};
+use crate::common::messages::{tr, MsgKey};
+
+/// KLU校验失败时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InvalidBarPolicy {
+    Skip,  // 丢弃该KLU
+    Error, // 返回错误
+    Clamp, // 能修正的修正后接收（价格截断到允许区间），修正不了的丢弃
+}
+
+/// 包含关系合并时取高高还是低低
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum CombineDirPolicy {
+    /// 按前一根KLC的方向，向上取高高、向下取低低
+    #[default]
+    Trend,
+    /// 按被合并KLU自身的涨跌，收阳取高高、收阴取低低，平盘时按前一根KLC的方向
+    Close,
+}
+
+/// 与前一根KLC高点（或低点）恰好相等的KLU如何处理，高低点都相等时总是合并
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum EqualExtremePolicy {
+    /// 按包含关系合并
+    #[default]
+    Merge,
+    /// 不算包含，按不相等的那一端定方向：高点相等时低点更低为向下，低点相等时高点更高为向上
+    Break,
+}
+
+/// 分形判定口径
+///
+/// 按 `EqualExtremePolicy::Merge` 处理过包含关系的相邻KLC高低点必然同向且不相等；
+/// `EqualExtremePolicy::Break` 下相邻KLC可能高点或低点相等，如 (10,8) 之后的 (10,7) 会开出一根向下的KLC
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FxConfig {
+    /// 相邻两个分形中间KLC的最小间隔，间隔不足的后一个分形不算，0 表示不限制
+    pub min_klc_gap: usize,
+    /// 中间KLC的高点和低点都要严格高于（顶）或低于（底）两侧；为 false 时按 `Combiner::update_fx`
+    /// 的 exclude_included 口径，不再要求前一根的另一端也被占优
+    pub strict: bool,
+    /// 中间KLC与后一根KLC高点相等时仍算顶、低点相等时仍算底，
+    /// 此时同样按 exclude_included 口径判定，`strict` 不起作用；默认相等即不成分形
+    pub allow_equal: bool,
+}
+
+impl Default for FxConfig {
+    fn default() -> Self {
+        Self {
+            min_klc_gap: 0,
+            strict: true,
+            allow_equal: false,
+        }
+    }
+}
+
+/// MACD参数，默认 12/26/9，同 chan.py
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MacdConfig {
+    pub fast: usize,
+    pub slow: usize,
+    pub signal: usize,
+}
+
+impl Default for MacdConfig {
+    fn default() -> Self {
+        Self {
+            fast: 12,
+            slow: 26,
+            signal: 9,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChanConfig {
+    pub bi_conf: BiConfig,
+    pub fx_conf: FxConfig,
+    pub combine_dir: CombineDirPolicy,
+    pub equal_extreme: EqualExtremePolicy,
+    pub macd: MacdConfig,
+    /// None 时不做任何校验，所有KLU照单全收
+    pub on_invalid_bar: Option<InvalidBarPolicy>,
+    /// 相对上一根收盘价的最大涨跌幅，超过视为异常跳变
+    pub max_price_change_rate: f64,
+    /// 允许连续成交量为0的KLU根数
+    pub max_zero_volume_cnt: usize,
+}
+
+impl Default for ChanConfig {
+    fn default() -> Self {
+        Self {
+            bi_conf: BiConfig::default(),
+            fx_conf: FxConfig::default(),
+            combine_dir: CombineDirPolicy::default(),
+            equal_extreme: EqualExtremePolicy::default(),
+            macd: MacdConfig::default(),
+            on_invalid_bar: None,
+            max_price_change_rate: f64::INFINITY,
+            max_zero_volume_cnt: usize::MAX,
+        }
+    }
+}
+
+impl ChanConfig {
+    /// `ChanConfig::builder().bi(|b| b.fx_check(FxCheckMethod::Half)).build()?`
+    pub fn builder() -> ChanConfigBuilder {
+        ChanConfigBuilder::new(Self::default())
+    }
+}
+
+/// 逐项设置 `ChanConfig`，非法取值记下第一个错误，在 `build` 时返回
+#[derive(Debug, Clone)]
+pub struct ChanConfigBuilder {
+    conf: ChanConfig,
+    err: Option<ChanException>,
+}
+
+impl ChanConfigBuilder {
+    pub fn new(conf: ChanConfig) -> Self {
+        Self { conf, err: None }
+    }
+
+    fn fail(&mut self, msg: String) {
+        if self.err.is_none() {
+            self.err = Some(ChanException::new(msg, ErrCode::ParaError));
+        }
+    }
+
+    /// 在当前笔配置的基础上修改
+    pub fn bi(mut self, f: impl FnOnce(BiConfigBuilder) -> BiConfigBuilder) -> Self {
+        match f(BiConfigBuilder::new(self.conf.bi_conf.clone())).build() {
+            Ok(bi_conf) => self.conf.bi_conf = bi_conf,
+            Err(err) => {
+                self.err.get_or_insert(err);
+            }
+        }
+        self
+    }
+
+    pub fn fx_min_klc_gap(mut self, min_klc_gap: usize) -> Self {
+        self.conf.fx_conf.min_klc_gap = min_klc_gap;
+        self
+    }
+
+    pub fn fx_strict(mut self, strict: bool) -> Self {
+        self.conf.fx_conf.strict = strict;
+        self
+    }
+
+    pub fn fx_allow_equal(mut self, allow_equal: bool) -> Self {
+        self.conf.fx_conf.allow_equal = allow_equal;
+        self
+    }
+
+    pub fn combine_dir(mut self, combine_dir: CombineDirPolicy) -> Self {
+        self.conf.combine_dir = combine_dir;
+        self
+    }
+
+    pub fn equal_extreme(mut self, policy: EqualExtremePolicy) -> Self {
+        self.conf.equal_extreme = policy;
+        self
+    }
+
+    pub fn macd(mut self, fast: usize, slow: usize, signal: usize) -> Self {
+        if fast > 0 && slow > 0 && signal > 0 {
+            self.conf.macd = MacdConfig { fast, slow, signal };
+        } else {
+            self.fail(tr(MsgKey::InvalidMacdPeriod, &[&fast, &slow, &signal]));
+        }
+        self
+    }
+
+    pub fn on_invalid_bar(mut self, policy: InvalidBarPolicy) -> Self {
+        self.conf.on_invalid_bar = Some(policy);
+        self
+    }
+
+    pub fn max_price_change_rate(mut self, rate: f64) -> Self {
+        if rate > 0.0 {
+            self.conf.max_price_change_rate = rate;
+        } else {
+            self.fail(tr(MsgKey::InvalidPriceChangeRate, &[&rate]));
+        }
+        self
+    }
+
+    pub fn max_zero_volume_cnt(mut self, cnt: usize) -> Self {
+        self.conf.max_zero_volume_cnt = cnt;
+        self
+    }
+
+    /// 按配置项名字设置，取值为文本，供配置文件和命令行使用；名字不认识或取值不合法时同样记下错误
+    ///
+    /// 布尔值接受 true/false/1/0，`macd` 写成 `fast,slow,signal`，其余枚举项取小写名字
+    pub fn set(mut self, key: &str, value: &str) -> Self {
+        match self.clone().try_set(key, value) {
+            Ok(builder) => builder,
+            Err(msg) => {
+                self.fail(msg);
+                self
+            }
+        }
+    }
+
+    fn try_set(self, key: &str, value: &str) -> Result<Self, String> {
+        let bad = || tr(MsgKey::InvalidConfigValue, &[&key, &value]);
+        let parse_bool = || match value {
+            "true" | "1" => Ok(true),
+            "false" | "0" => Ok(false),
+            _ => Err(bad()),
+        };
+        Ok(match key {
+            "bi_algo" => self.bi(|b| b.bi_algo(value)),
+            "bi_strict" => {
+                let is_strict = parse_bool()?;
+                self.bi(|b| b.is_strict(is_strict))
+            }
+            "bi_fx_check" => {
+                let fx_check = value.parse().map_err(|e: ChanException| e.msg)?;
+                self.bi(|b| b.fx_check(fx_check))
+            }
+            "gap_as_kl" => {
+                let gap_as_kl = parse_bool()?;
+                self.bi(|b| b.gap_as_kl(gap_as_kl))
+            }
+            "bi_end_is_peak" => {
+                let end_is_peak = parse_bool()?;
+                self.bi(|b| b.end_is_peak(end_is_peak))
+            }
+            "bi_allow_sub_peak" => {
+                let allow_sub_peak = parse_bool()?;
+                self.bi(|b| b.allow_sub_peak(allow_sub_peak))
+            }
+            "fx_min_klc_gap" => self.fx_min_klc_gap(value.parse().map_err(|_| bad())?),
+            "fx_strict" => self.fx_strict(parse_bool()?),
+            "fx_allow_equal" => self.fx_allow_equal(parse_bool()?),
+            "combine_dir" => self.combine_dir(match value {
+                "trend" => CombineDirPolicy::Trend,
+                "close" => CombineDirPolicy::Close,
+                _ => return Err(bad()),
+            }),
+            "equal_extreme" => self.equal_extreme(match value {
+                "merge" => EqualExtremePolicy::Merge,
+                "break" => EqualExtremePolicy::Break,
+                _ => return Err(bad()),
+            }),
+            "macd" => {
+                let periods = value
+                    .split(',')
+                    .map(|p| p.trim().parse().map_err(|_| bad()))
+                    .collect::<Result<Vec<usize>, _>>()?;
+                match periods[..] {
+                    [fast, slow, signal] => self.macd(fast, slow, signal),
+                    _ => return Err(bad()),
+                }
+            }
+            "on_invalid_bar" => self.on_invalid_bar(match value {
+                "skip" => InvalidBarPolicy::Skip,
+                "error" => InvalidBarPolicy::Error,
+                "clamp" => InvalidBarPolicy::Clamp,
+                _ => return Err(bad()),
+            }),
+            "max_price_change_rate" => {
+                self.max_price_change_rate(value.parse().map_err(|_| bad())?)
+            }
+            "max_zero_volume_cnt" => self.max_zero_volume_cnt(value.parse().map_err(|_| bad())?),
+            _ => return Err(tr(MsgKey::UnknownConfigKey, &[&key])),
+        })
+    }
+
+    pub fn build(self) -> ChanResult<ChanConfig> {
+        match self.err {
+            Some(err) => Err(err),
+            None => Ok(self.conf),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::enums::FxCheckMethod;
+
+    #[test]
+    fn test_builder() {
+        let conf = ChanConfig::builder()
+            .bi(|b| b.fx_check(FxCheckMethod::Half).is_strict(false))
+            .on_invalid_bar(InvalidBarPolicy::Skip)
+            .max_price_change_rate(0.1)
+            .build()
+            .unwrap();
+        assert_eq!(conf.bi_conf.bi_fx_check, FxCheckMethod::Half);
+        assert!(!conf.bi_conf.is_strict);
+        // 未设置的项保持默认
+        assert!(conf.bi_conf.bi_end_is_peak);
+        assert_eq!(conf.on_invalid_bar, Some(InvalidBarPolicy::Skip));
+
+        // 记下的是第一个错误
+        let err = ChanConfig::builder()
+            .max_price_change_rate(f64::NAN)
+            .bi(|b| b.bi_algo("xxx"))
+            .build()
+            .unwrap_err();
+        assert!(err.msg.contains("max_price_change_rate"));
+    }
+
+    #[test]
+    fn test_set() {
+        let conf = ChanConfig::builder()
+            .set("bi_fx_check", "loss")
+            .set("gap_as_kl", "1")
+            .set("equal_extreme", "break")
+            .set("fx_allow_equal", "true")
+            .set("fx_strict", "0")
+            .set("macd", "5, 10, 3")
+            .build()
+            .unwrap();
+        assert_eq!(conf.bi_conf.bi_fx_check, FxCheckMethod::Loss);
+        assert!(conf.bi_conf.gap_as_kl);
+        assert_eq!(conf.equal_extreme, EqualExtremePolicy::Break);
+        assert!(conf.fx_conf.allow_equal);
+        assert!(!conf.fx_conf.strict);
+        assert!(ChanConfig::default().fx_conf.strict);
+        assert_eq!(
+            (conf.macd.fast, conf.macd.slow, conf.macd.signal),
+            (5, 10, 3)
+        );
+
+        for (key, value) in [
+            ("bi_fx_check", "xxx"),
+            ("bi_strict", "yes"),
+            ("macd", "1,2"),
+            ("xxx", "1"),
+        ] {
+            let err = ChanConfig::builder().set(key, value).build().unwrap_err();
+            assert_eq!(err.errcode, ErrCode::ParaError);
+            assert!(err.msg.contains(key));
+        }
+    }
+
+    #[test]
+    fn test_default_bi_conf() {
+        // 两处默认值必须一致，都以 chan.py 为准
+        let conf = ChanConfig::default();
+        assert_eq!(conf.bi_conf, BiConfig::default());
+        assert_eq!(
+            ChanConfig::builder().build().unwrap().bi_conf,
+            BiConfig::default()
+        );
+        assert_eq!(conf.bi_conf.bi_fx_check, FxCheckMethod::Strict);
+        assert!(!conf.bi_conf.gap_as_kl);
+    }
+}
@@ -0,0 +1,34 @@
+use crate::common::ctime::CTime;
+use crate::kline::kline_unit::KLineUnit;
+
+/// 可以参与包含关系合并的元素：KLU合并成KLC，笔/线段合并成特征序列
+pub trait AsCombineItem {
+    /// 元素在其所属列表中的下标
+    fn item_idx(&self) -> usize;
+    fn time_begin(&self) -> CTime;
+    fn time_end(&self) -> CTime;
+    fn high(&self) -> f64;
+    fn low(&self) -> f64;
+}
+
+impl AsCombineItem for KLineUnit {
+    fn item_idx(&self) -> usize {
+        self.idx()
+    }
+
+    fn time_begin(&self) -> CTime {
+        self.time
+    }
+
+    fn time_end(&self) -> CTime {
+        self.time
+    }
+
+    fn high(&self) -> f64 {
+        self.high
+    }
+
+    fn low(&self) -> f64 {
+        self.low
+    }
+}
@@ -0,0 +1,349 @@
+use std::marker::PhantomData;
+
+use crate::combiner::combine_item::AsCombineItem;
+use crate::common::chan_exception::{ChanException, ChanResult, ErrCode};
+use crate::common::ctime::CTime;
+use crate::common::enums::{FxType, KLineDir};
+use crate::common::messages::{tr, MsgKey};
+
+/// 包含关系合并器，只记录被合并元素的下标，元素本身由外部列表持有
+#[derive(Debug, Clone)]
+pub struct Combiner<T> {
+    time_begin: CTime,
+    time_end: CTime,
+    high: f64,
+    low: f64,
+    lst: Vec<usize>,
+    dir: KLineDir,
+    fx: FxType,
+    _item: PhantomData<fn() -> T>,
+}
+
+impl<T: AsCombineItem> Combiner<T> {
+    pub fn new(item: &T, dir: KLineDir) -> Self {
+        Self {
+            time_begin: item.time_begin(),
+            time_end: item.time_end(),
+            high: item.high(),
+            low: item.low(),
+            lst: vec![item.item_idx()],
+            dir,
+            fx: FxType::Unknown,
+            _item: PhantomData,
+        }
+    }
+
+    /// 按已有的状态直接构造，恢复快照时使用
+    pub(crate) fn from_parts(
+        time_begin: CTime,
+        time_end: CTime,
+        high: f64,
+        low: f64,
+        lst: Vec<usize>,
+        dir: KLineDir,
+        fx: FxType,
+    ) -> Self {
+        Self {
+            time_begin,
+            time_end,
+            high,
+            low,
+            lst,
+            dir,
+            fx,
+            _item: PhantomData,
+        }
+    }
+
+    pub fn time_begin(&self) -> CTime {
+        self.time_begin
+    }
+
+    pub fn time_end(&self) -> CTime {
+        self.time_end
+    }
+
+    pub fn high(&self) -> f64 {
+        self.high
+    }
+
+    pub fn low(&self) -> f64 {
+        self.low
+    }
+
+    pub fn dir(&self) -> KLineDir {
+        self.dir
+    }
+
+    pub fn fx(&self) -> FxType {
+        self.fx
+    }
+
+    /// 被合并元素的下标
+    pub fn lst(&self) -> &[usize] {
+        &self.lst
+    }
+
+    pub fn len(&self) -> usize {
+        self.lst.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lst.is_empty()
+    }
+
+    pub(crate) fn set_fx(&mut self, fx: FxType) {
+        self.fx = fx;
+    }
+
+    /// 删除前面的元素之后整体平移下标
+    pub(crate) fn shift_item_idx(&mut self, offset: usize) {
+        for idx in &mut self.lst {
+            *idx -= offset;
+        }
+    }
+
+    /// allow_top_equal = None普通模式
+    /// allow_top_equal = Some(1) 被包含，顶部相等不合并
+    /// allow_top_equal = Some(-1) 被包含，底部相等不合并
+    pub fn test_combine(
+        &self,
+        item: &T,
+        exclude_included: bool,
+        allow_top_equal: Option<i32>,
+    ) -> ChanResult<KLineDir> {
+        let (high, low) = (item.high(), item.low());
+        if self.high >= high && self.low <= low {
+            return Ok(KLineDir::Combine);
+        }
+        if self.high <= high && self.low >= low {
+            if allow_top_equal == Some(1) && self.high == high && self.low > low {
+                return Ok(KLineDir::Down);
+            } else if allow_top_equal == Some(-1) && self.low == low && self.high < high {
+                return Ok(KLineDir::Up);
+            }
+            return Ok(if exclude_included {
+                KLineDir::Included
+            } else {
+                KLineDir::Combine
+            });
+        }
+        if self.high > high && self.low > low {
+            return Ok(KLineDir::Down);
+        }
+        if self.high < high && self.low < low {
+            return Ok(KLineDir::Up);
+        }
+        Err(ChanException::new(
+            tr(MsgKey::CombineTypeUnknown, &[]),
+            ErrCode::CombinerErr,
+        ))
+    }
+
+    /// 尝试把 `item` 合并进来，返回 Up/Down/Combine，用于设置下一个合并元素的方向
+    ///
+    /// 合并时按当前方向取高高/低低，一字K线不改变高低点
+    pub fn try_add(
+        &mut self,
+        item: &T,
+        exclude_included: bool,
+        allow_top_equal: Option<i32>,
+    ) -> ChanResult<KLineDir> {
+        self.try_add_with_dir(item, exclude_included, allow_top_equal, self.dir)
+    }
+
+    /// 同 `try_add`，但合并时按 `merge_dir` 取高高/低低，合并元素本身的方向不变
+    pub fn try_add_with_dir(
+        &mut self,
+        item: &T,
+        exclude_included: bool,
+        allow_top_equal: Option<i32>,
+        merge_dir: KLineDir,
+    ) -> ChanResult<KLineDir> {
+        let dir = self.test_combine(item, exclude_included, allow_top_equal)?;
+        if dir == KLineDir::Combine {
+            self.merge(item, merge_dir)?;
+        }
+        Ok(dir)
+    }
+
+    /// 按 `dir` 取高高/低低把 `item` 并进来，不检查包含关系
+    fn merge(&mut self, item: &T, dir: KLineDir) -> ChanResult<()> {
+        let (high, low) = (item.high(), item.low());
+        match dir {
+            KLineDir::Up => {
+                if high != low || high != self.high {
+                    self.high = self.high.max(high);
+                    self.low = self.low.max(low);
+                }
+            }
+            KLineDir::Down => {
+                if high != low || low != self.low {
+                    self.high = self.high.min(high);
+                    self.low = self.low.min(low);
+                }
+            }
+            _ => {
+                return Err(ChanException::new(
+                    tr(MsgKey::CombineDirErr, &[&format!("{dir:?}")]),
+                    ErrCode::CombinerErr,
+                ))
+            }
+        }
+        self.lst.push(item.item_idx());
+        self.time_end = item.time_end();
+        Ok(())
+    }
+
+    /// 用 `idxs` 对应的元素按 `dir` 重新合并出一个合并元素
+    fn rebuild(items: &[T], idxs: &[usize], dir: KLineDir) -> ChanResult<Self> {
+        let mut combiner = Self::new(&items[idxs[0]], dir);
+        for &idx in &idxs[1..] {
+            combiner.merge(&items[idx], dir)?;
+        }
+        Ok(combiner)
+    }
+
+    /// 获取最大值 or 最小值所在的元素，有多个时取最后一个；`items` 为按下标存放元素的列表
+    pub fn get_peak_klu<'a>(&self, items: &'a [T], is_high: bool) -> ChanResult<&'a T> {
+        self.lst
+            .iter()
+            .rev()
+            .map(|&idx| &items[idx])
+            .find(|item| {
+                if is_high {
+                    item.high() == self.high
+                } else {
+                    item.low() == self.low
+                }
+            })
+            .ok_or_else(|| ChanException::new(tr(MsgKey::PeakNotFound, &[]), ErrCode::CombinerErr))
+    }
+
+    pub fn get_high_peak_klu<'a>(&self, items: &'a [T]) -> ChanResult<&'a T> {
+        self.get_peak_klu(items, true)
+    }
+
+    pub fn get_low_peak_klu<'a>(&self, items: &'a [T]) -> ChanResult<&'a T> {
+        self.get_peak_klu(items, false)
+    }
+
+    /// 在极值元素处把合并元素一拆为二：自己只保留到极值元素为止，
+    /// 极值之后的元素按反方向重新合并后返回，极值已经是最后一个元素时返回 None
+    ///
+    /// 特征序列需要精确定位线段端点对应的元素时使用
+    pub fn split_at_peak(&mut self, items: &[T], is_high: bool) -> ChanResult<Option<Self>> {
+        let peak_idx = self.get_peak_klu(items, is_high)?.item_idx();
+        let pos = self
+            .lst
+            .iter()
+            .position(|&idx| idx == peak_idx)
+            .expect("peak must be in lst");
+        if pos + 1 == self.lst.len() {
+            return Ok(None);
+        }
+        let rest_dir = if is_high {
+            KLineDir::Down
+        } else {
+            KLineDir::Up
+        };
+        let rest = Self::rebuild(items, &self.lst[pos + 1..], rest_dir)?;
+        let mut head = Self::rebuild(items, &self.lst[..=pos], self.dir)?;
+        head.fx = self.fx;
+        *self = head;
+        Ok(Some(rest))
+    }
+
+    /// 用前后两个合并元素判断自己是否为顶底分形
+    ///
+    /// exclude_included 为 true 时只要求高点（低点）严格占优，
+    /// 此时 allow_top_equal = Some(1) 允许右侧顶部相等，Some(-1) 允许右侧底部相等
+    pub fn update_fx(
+        &mut self,
+        pre: &Self,
+        next: &Self,
+        exclude_included: bool,
+        allow_top_equal: Option<i32>,
+    ) {
+        if exclude_included {
+            if pre.high < self.high && next.high <= self.high && next.low < self.low {
+                if allow_top_equal == Some(1) || next.high < self.high {
+                    self.fx = FxType::Top;
+                }
+            } else if next.high > self.high
+                && pre.low > self.low
+                && next.low >= self.low
+                && (allow_top_equal == Some(-1) || next.low > self.low)
+            {
+                self.fx = FxType::Bottom;
+            }
+        } else if pre.high < self.high
+            && next.high < self.high
+            && pre.low < self.low
+            && next.low < self.low
+        {
+            self.fx = FxType::Top;
+        } else if pre.high > self.high
+            && next.high > self.high
+            && pre.low > self.low
+            && next.low > self.low
+        {
+            self.fx = FxType::Bottom;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kline::kline_unit::KLineUnit;
+    use crate::kline::trade_info::TradeInfo;
+
+    fn klus(bars: &[(f64, f64)]) -> Vec<KLineUnit> {
+        bars.iter()
+            .enumerate()
+            .map(|(i, &(high, low))| {
+                let t = CTime::new(2024, 1, 1 + i as u32, 0, 0, 0, false);
+                let mut klu =
+                    KLineUnit::new(t, low, high, low, high, TradeInfo::default(), false).unwrap();
+                klu.idx = i;
+                klu
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_get_peak_klu() {
+        let items = klus(&[(10.0, 8.0), (10.0, 9.0), (9.8, 9.2)]);
+        let mut combiner = Combiner::new(&items[0], KLineDir::Up);
+        for item in &items[1..] {
+            assert_eq!(
+                combiner.try_add(item, false, None).unwrap(),
+                KLineDir::Combine
+            );
+        }
+        assert_eq!(combiner.lst(), &[0, 1, 2]);
+        // 高点相同取最后一个
+        assert_eq!(combiner.get_peak_klu(&items, true).unwrap().idx(), 1);
+        assert_eq!(combiner.get_peak_klu(&items, false).unwrap().idx(), 2);
+    }
+
+    #[test]
+    fn test_split_at_peak() {
+        let items = klus(&[(10.0, 8.0), (11.0, 7.0), (10.5, 9.0), (10.2, 9.5)]);
+        let mut combiner = Combiner::new(&items[0], KLineDir::Up);
+        for item in &items[1..] {
+            combiner.try_add(item, false, None).unwrap();
+        }
+        assert_eq!((combiner.high(), combiner.low()), (11.0, 9.5));
+        let rest = combiner.split_at_peak(&items, true).unwrap().unwrap();
+        assert_eq!(combiner.lst(), &[0, 1]);
+        assert_eq!((combiner.high(), combiner.low()), (11.0, 8.0));
+        assert_eq!(combiner.time_end(), items[1].time);
+        assert_eq!(rest.lst(), &[2, 3]);
+        assert_eq!(rest.dir(), KLineDir::Down);
+        assert_eq!((rest.high(), rest.low()), (10.2, 9.0));
+        // 极值已在末尾时不拆分
+        assert!(combiner.split_at_peak(&items, true).unwrap().is_none());
+    }
+}
@@ -0,0 +1,2 @@
+pub mod combine_item;
+pub mod kline_combiner;
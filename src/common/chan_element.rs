@@ -0,0 +1,19 @@
+use crate::common::enums::KLineDir;
+
+/// KLC、笔、线段、中枢等元素的公共接口，用于写与具体类型无关的查询、导出工具
+pub trait ChanElement {
+    fn idx(&self) -> usize;
+    fn begin_ts(&self) -> i64;
+    fn end_ts(&self) -> i64;
+    fn high(&self) -> f64;
+    fn low(&self) -> f64;
+    fn dir(&self) -> KLineDir;
+    fn is_sure(&self) -> bool;
+}
+
+/// 按时间递增排列的 `lst` 中与 [begin_ts, end_ts] 有重叠的元素
+pub fn query_range<T: ChanElement>(lst: &[T], begin_ts: i64, end_ts: i64) -> &[T] {
+    let begin = lst.partition_point(|e| e.end_ts() < begin_ts);
+    let end = lst.partition_point(|e| e.begin_ts() <= end_ts);
+    &lst[begin..end.max(begin)]
+}
@@ -0,0 +1,113 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrCode {
+    // chan err
+    ChanErrBegin = 0,
+    CommonError = 1,
+    SrcDataNotFound = 3,
+    SrcDataTypeErr = 4,
+    ParaError = 5,
+    ExtraKluErr = 6,
+    SegEndValueErr = 7,
+    SegEigenErr = 8,
+    BiErr = 9,
+    CombinerErr = 10,
+    PlotErr = 11,
+    ModelError = 12,
+    SegLenErr = 13,
+    EnvConfErr = 14,
+    UnknownDbType = 15,
+    FeatureError = 16,
+    ConfigError = 17,
+    SrcDataFormatError = 18,
+    ChanErrEnd = 99,
+
+    // Trade Error
+    TradeErrBegin = 100,
+    SignalExisted = 101,
+    RecordNotExist = 102,
+    RecordAlreadyOpened = 103,
+    QuotaNotEnough = 104,
+    RecordNotOpened = 105,
+    TradeUnlockFail = 106,
+    PlaceOrderFail = 107,
+    ListOrderFail = 108,
+    CandelOrderFail = 109,
+    GetFutuPriceFail = 110,
+    GetFutuLotSizeFail = 111,
+    OpenRecordNotWatching = 112,
+    GetHoldingQtyFail = 113,
+    RecordClosed = 114,
+    RequestTradingDaysFail = 115,
+    CoverOrderIdNotUnique = 116,
+    SignalTraded = 117,
+    TradeErrEnd = 199,
+
+    // KL data Error
+    KlErrBegin = 200,
+    PriceBelowZero = 201,
+    KlDataNotAlign = 202,
+    KlDataInvalid = 203,
+    KlTimeInconsistent = 204,
+    TradeinfoTooMuchZero = 205,
+    KlNotMonotonous = 206,
+    SnapshotErr = 207,
+    Suspension = 208, // 疑似停牌
+    StockIpoTooLate = 209,
+    NoData = 210,
+    StockNotActive = 211,
+    StockPriceNotActive = 212,
+    PriceNotOnTick = 213, // 价格不在最小变动价位上
+    KlErrEnd = 299,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChanException {
+    pub errcode: ErrCode,
+    pub msg: String,
+}
+
+pub type ChanResult<T> = Result<T, ChanException>;
+
+impl ChanException {
+    pub fn new(msg: impl Into<String>, errcode: ErrCode) -> Self {
+        Self {
+            errcode,
+            msg: msg.into(),
+        }
+    }
+
+    pub fn is_kldata_err(&self) -> bool {
+        let code = self.errcode as i32;
+        (ErrCode::KlErrBegin as i32) < code && code < ErrCode::KlErrEnd as i32
+    }
+
+    pub fn is_chan_err(&self) -> bool {
+        let code = self.errcode as i32;
+        (ErrCode::ChanErrBegin as i32) < code && code < ErrCode::ChanErrEnd as i32
+    }
+}
+
+impl fmt::Display for ChanException {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+impl std::error::Error for ChanException {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_err_category() {
+        let e = ChanException::new("bad bar", ErrCode::KlDataInvalid);
+        assert!(e.is_kldata_err());
+        assert!(!e.is_chan_err());
+        let e = ChanException::new("bad config", ErrCode::ConfigError);
+        assert!(e.is_chan_err());
+        assert_eq!(e.to_string(), "bad config");
+    }
+}
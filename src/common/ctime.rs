@@ -0,0 +1,169 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+/// K线时间，比较和排序都只看 `ts`（按 UTC 计算的秒级时间戳）
+#[derive(Debug, Clone, Copy)]
+pub struct CTime {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    pub auto: bool, // 自适应对天的理解
+    ts: i64,
+}
+
+impl CTime {
+    pub fn new(
+        year: i32,
+        month: u32,
+        day: u32,
+        hour: u32,
+        minute: u32,
+        second: u32,
+        auto: bool,
+    ) -> Self {
+        let mut t = Self {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            auto,
+            ts: 0,
+        };
+        t.set_timestamp();
+        t
+    }
+
+    /// 由时间戳反推年月日时分秒，`auto` 固定为 false
+    pub fn from_ts(ts: i64) -> Self {
+        let (year, month, day) = civil_from_days(ts.div_euclid(86400));
+        let secs = ts.rem_euclid(86400) as u32;
+        Self::new(
+            year,
+            month,
+            day,
+            secs / 3600,
+            secs % 3600 / 60,
+            secs % 60,
+            false,
+        )
+    }
+
+    pub fn ts(&self) -> i64 {
+        self.ts
+    }
+
+    pub fn to_str(&self) -> String {
+        self.to_string()
+    }
+
+    pub fn to_date_str(&self, splt: &str) -> String {
+        format!(
+            "{:04}{splt}{:02}{splt}{:02}",
+            self.year, self.month, self.day
+        )
+    }
+
+    pub fn to_date(&self) -> CTime {
+        CTime::new(self.year, self.month, self.day, 0, 0, 0, false)
+    }
+
+    fn set_timestamp(&mut self) {
+        let (hour, minute) = if self.hour == 0 && self.minute == 0 && self.auto {
+            (23, 59)
+        } else {
+            (self.hour, self.minute)
+        };
+        let days = days_from_civil(self.year, self.month, self.day);
+        self.ts = days * 86400 + hour as i64 * 3600 + minute as i64 * 60 + self.second as i64;
+    }
+}
+
+// http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+fn days_from_civil(year: i32, month: u32, day: u32) -> i64 {
+    let y = if month <= 2 {
+        year as i64 - 1
+    } else {
+        year as i64
+    };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400;
+    let m = month as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i32, u32, u32) {
+    let z = days + 719468;
+    let era = if z >= 0 { z } else { z - 146096 } / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year as i32, month, day)
+}
+
+impl fmt::Display for CTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.hour == 0 && self.minute == 0 {
+            write!(f, "{:04}/{:02}/{:02}", self.year, self.month, self.day)
+        } else {
+            write!(
+                f,
+                "{:04}/{:02}/{:02} {:02}:{:02}",
+                self.year, self.month, self.day, self.hour, self.minute
+            )
+        }
+    }
+}
+
+impl PartialEq for CTime {
+    fn eq(&self, other: &Self) -> bool {
+        self.ts == other.ts
+    }
+}
+
+impl Eq for CTime {}
+
+impl PartialOrd for CTime {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for CTime {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.ts.cmp(&other.ts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ts_and_str() {
+        let t = CTime::new(2024, 3, 1, 9, 30, 0, false);
+        assert_eq!(t.ts(), 1709285400);
+        assert_eq!(t.to_str(), "2024/03/01 09:30");
+        assert_eq!(t.to_date().to_str(), "2024/03/01");
+        assert_eq!(t.to_date_str("-"), "2024-03-01");
+
+        let day = CTime::new(2024, 3, 1, 0, 0, 0, true);
+        assert!(day > t);
+
+        let back = CTime::from_ts(t.ts());
+        assert_eq!((back.year, back.month, back.day), (2024, 3, 1));
+        assert_eq!((back.hour, back.minute, back.second), (9, 30, 0));
+        assert_eq!(CTime::from_ts(day.ts()).to_str(), "2024/03/01 23:59");
+    }
+}
@@ -0,0 +1,119 @@
+use std::str::FromStr;
+
+use crate::common::chan_exception::{ChanException, ErrCode};
+use crate::common::messages::{tr, MsgKey};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum KLineType {
+    /// 逐笔成交，每笔一根KLU，同一秒内可以有多笔
+    KTick = 0,
+    K1S = 1,
+    K3S,
+    K5S,
+    K10S,
+    K15S,
+    K20S,
+    K30S,
+    K1M,
+    K3M,
+    K5M,
+    K10M,
+    K15M,
+    K30M,
+    K60M,
+    KDay,
+    KWeek,
+    KMon,
+    KQuarter,
+    KYear,
+}
+
+impl KLineType {
+    pub const ALL: [KLineType; 20] = [
+        KLineType::KTick,
+        KLineType::K1S,
+        KLineType::K3S,
+        KLineType::K5S,
+        KLineType::K10S,
+        KLineType::K15S,
+        KLineType::K20S,
+        KLineType::K30S,
+        KLineType::K1M,
+        KLineType::K3M,
+        KLineType::K5M,
+        KLineType::K10M,
+        KLineType::K15M,
+        KLineType::K30M,
+        KLineType::K60M,
+        KLineType::KDay,
+        KLineType::KWeek,
+        KLineType::KMon,
+        KLineType::KQuarter,
+        KLineType::KYear,
+    ];
+
+    /// 按数值（与 chan.py 的 KL_TYPE 取值一致）查找
+    pub fn from_value(value: i32) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|kl_type| *kl_type as i32 == value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KLineDir {
+    Up,
+    Down,
+    Combine,
+    Included,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FxType {
+    Bottom,
+    Top,
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FxCheckMethod {
+    Strict,
+    Loss,
+    Half,
+    Totally,
+}
+
+/// 按 chan.py 的配置取值解析：strict/loss/half/totally
+impl FromStr for FxCheckMethod {
+    type Err = ChanException;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "strict" => Ok(Self::Strict),
+            "loss" => Ok(Self::Loss),
+            "half" => Ok(Self::Half),
+            "totally" => Ok(Self::Totally),
+            _ => Err(ChanException::new(
+                tr(MsgKey::UnknownFxCheck, &[&s]),
+                ErrCode::ParaError,
+            )),
+        }
+    }
+}
+
+pub struct DataField;
+
+impl DataField {
+    pub const FIELD_TIME: &'static str = "time_key";
+    pub const FIELD_OPEN: &'static str = "open";
+    pub const FIELD_HIGH: &'static str = "high";
+    pub const FIELD_LOW: &'static str = "low";
+    pub const FIELD_CLOSE: &'static str = "close";
+    pub const FIELD_VOLUME: &'static str = "volume"; // 成交量
+    pub const FIELD_TURNOVER: &'static str = "turnover"; // 成交额
+    pub const FIELD_TURNRATE: &'static str = "turnover_rate"; // 换手率
+    pub const FIELD_OPEN_INTEREST: &'static str = "open_interest"; // 持仓量
+    pub const FIELD_BID: &'static str = "bid";
+    pub const FIELD_ASK: &'static str = "ask";
+    pub const FIELD_TRADE_COUNT: &'static str = "trade_count"; // 成交笔数
+}
@@ -0,0 +1,28 @@
+use crate::common::chan_exception::{ChanException, ChanResult, ErrCode};
+use crate::common::enums::KLineType;
+use crate::common::messages::{tr, MsgKey};
+
+/// 两个区间是否有重叠，`equal` 为 true 时端点相等也算重叠
+pub fn has_overlap(l1: f64, h1: f64, l2: f64, h2: f64, equal: bool) -> bool {
+    if equal {
+        h2 >= l1 && h1 >= l2
+    } else {
+        h2 > l1 && h1 > l2
+    }
+}
+
+/// 是否是天级别以下，同 chan.py 的 kltype_lt_day
+pub fn kltype_lt_day(kl_type: KLineType) -> bool {
+    kl_type < KLineType::KDay
+}
+
+/// 多级别的级别列表必须从大到小严格排列
+pub fn check_kltype_order(lv_list: &[KLineType]) -> ChanResult<()> {
+    if lv_list.windows(2).any(|w| w[1] >= w[0]) {
+        return Err(ChanException::new(
+            tr(MsgKey::KlTypeOrder, &[&format!("{lv_list:?}")]),
+            ErrCode::ParaError,
+        ));
+    }
+    Ok(())
+}
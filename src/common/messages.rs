@@ -0,0 +1,211 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// 报错信息的语言
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Lang {
+    #[default]
+    En,
+    Zh,
+}
+
+static LANG: AtomicU8 = AtomicU8::new(Lang::En as u8);
+
+/// 全局切换报错信息语言，只影响之后生成的报错
+pub fn set_lang(lang: Lang) {
+    LANG.store(lang as u8, Ordering::Relaxed);
+}
+
+pub fn lang() -> Lang {
+    match LANG.load(Ordering::Relaxed) {
+        x if x == Lang::Zh as u8 => Lang::Zh,
+        _ => Lang::En,
+    }
+}
+
+/// 所有面向用户的报错信息
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MsgKey {
+    UnknownFxCheck,
+    UnknownBiAlgo,
+    InvalidPriceChangeRate,
+    CombineTypeUnknown,
+    CombineDirErr,
+    PeakNotFound,
+    LowNotMin,
+    HighNotMax,
+    PriceMissing,
+    PriceBelowZero,
+    TimeNotMonotonous,
+    PriceJump,
+    Suspension,
+    TooMuchZeroVolume,
+    MergeLevelMismatch,
+    SubLevelNotLower,
+    SubLevelNotAlign,
+    UnknownTimeFormat,
+    FileNotExist,
+    FileFormatError,
+    InvalidSymbolInfo,
+    InvalidBarSize,
+    WarmUpPanicked,
+    WarmUpTaken,
+    KlTypeNotSupported,
+    KlTypeOrder,
+    SnapshotVersion,
+    SnapshotCorrupted,
+    BatchJobPanicked,
+    InvalidMacdPeriod,
+    UnknownConfigKey,
+    InvalidConfigValue,
+    PriceNotOnTick,
+    ReconstructTrimmed,
+    TooManyBricks,
+    MergeOverlap,
+    ReconstructRebuilt,
+    TickFinerThanPrecision,
+    RollbackFailed,
+}
+
+impl MsgKey {
+    /// 模板中的 `{}` 按顺序替换为参数，各语言参数顺序一致
+    pub fn template(self, lang: Lang) -> &'static str {
+        use MsgKey::*;
+        match (self, lang) {
+            (UnknownFxCheck, Lang::En) => "unknown bi_fx_check={}",
+            (UnknownFxCheck, Lang::Zh) => "未知的 bi_fx_check={}",
+            (UnknownBiAlgo, Lang::En) => "unknown bi_algo={}",
+            (UnknownBiAlgo, Lang::Zh) => "未知的 bi_algo={}",
+            (InvalidPriceChangeRate, Lang::En) => "max_price_change_rate={} must be positive",
+            (InvalidPriceChangeRate, Lang::Zh) => "max_price_change_rate={} 必须为正数",
+            (CombineTypeUnknown, Lang::En) => "combine type unknown",
+            (CombineTypeUnknown, Lang::Zh) => "无法判断合并类型",
+            (CombineDirErr, Lang::En) => "KLINE_DIR = {} err!!! must be Up/Down",
+            (CombineDirErr, Lang::Zh) => "KLINE_DIR = {} 错误！！！必须是 Up/Down",
+            (PeakNotFound, Lang::En) => "can't find peak...",
+            (PeakNotFound, Lang::Zh) => "找不到极值点...",
+            (LowNotMin, Lang::En) => {
+                "{} low price={} is not min of [low={}, open={}, high={}, close={}]"
+            }
+            (LowNotMin, Lang::Zh) => {
+                "{} 最低价={} 不是 [low={}, open={}, high={}, close={}] 中的最小值"
+            }
+            (HighNotMax, Lang::En) => {
+                "{} high price={} is not max of [low={}, open={}, high={}, close={}]"
+            }
+            (HighNotMax, Lang::Zh) => {
+                "{} 最高价={} 不是 [low={}, open={}, high={}, close={}] 中的最大值"
+            }
+            (PriceMissing, Lang::En) => "{} {} price is missing",
+            (PriceMissing, Lang::Zh) => "{} 缺少 {} 价格",
+            (PriceBelowZero, Lang::En) => "{} low price={} is below zero",
+            (PriceBelowZero, Lang::Zh) => "{} 最低价={} 小于0",
+            (TimeNotMonotonous, Lang::En) => "kline time err, cur={}, last={}",
+            (TimeNotMonotonous, Lang::Zh) => "K线时间错误，当前={}，上一根={}",
+            (PriceJump, Lang::En) => "{} price jumps {}% from last close={}",
+            (PriceJump, Lang::Zh) => "{} 价格跳变 {}%，上一根收盘价={}",
+            (Suspension, Lang::En) => "{} suspected suspension",
+            (Suspension, Lang::Zh) => "{} 疑似停牌",
+            (TooMuchZeroVolume, Lang::En) => "{} {} consecutive klines have zero volume",
+            (TooMuchZeroVolume, Lang::Zh) => "{} 连续{}根K线成交量为0",
+            (MergeLevelMismatch, Lang::En) => "can't merge {} klines into {}",
+            (MergeLevelMismatch, Lang::Zh) => "不能把{}级别K线合并到{}级别",
+            (SubLevelNotLower, Lang::En) => "sub level {} must be lower than {}",
+            (SubLevelNotLower, Lang::Zh) => "次级别{}必须低于{}",
+            (SubLevelNotAlign, Lang::En) => "more than {} klines can't find sub level klines!!",
+            (SubLevelNotAlign, Lang::Zh) => "在次级别找不到K线条数超过{}！！",
+            (UnknownTimeFormat, Lang::En) => "unknown time column from csv:{}",
+            (UnknownTimeFormat, Lang::Zh) => "无法识别的csv时间列:{}",
+            (FileNotExist, Lang::En) => "file not exist: {}",
+            (FileNotExist, Lang::Zh) => "文件不存在: {}",
+            (FileFormatError, Lang::En) => "file format error: {} line {}",
+            (FileFormatError, Lang::Zh) => "文件格式错误: {} 第{}行",
+            (InvalidSymbolInfo, Lang::En) => "{}: tick_size={} and lot_size={} must be positive",
+            (InvalidSymbolInfo, Lang::Zh) => "{}: tick_size={} 和 lot_size={} 必须为正数",
+            (InvalidBarSize, Lang::En) => "bar size must be positive, got {}",
+            (InvalidBarSize, Lang::Zh) => "K线合成尺寸必须为正数，当前为 {}",
+            (WarmUpPanicked, Lang::En) => "warm-up thread panicked",
+            (WarmUpPanicked, Lang::Zh) => "历史数据预热线程异常退出",
+            (WarmUpTaken, Lang::En) => "warm-up result has already been taken",
+            (WarmUpTaken, Lang::Zh) => "预热结果已被取走",
+            (KlTypeNotSupported, Lang::En) => "{} does not support kl_type {}",
+            (KlTypeNotSupported, Lang::Zh) => "{} 不支持K线级别 {}",
+            (KlTypeOrder, Lang::En) => "lv_list must go from higher to lower level: {}",
+            (KlTypeOrder, Lang::Zh) => "lv_list的顺序必须从大级别到小级别: {}",
+            (SnapshotVersion, Lang::En) => "unsupported snapshot version {}, expected {}",
+            (SnapshotVersion, Lang::Zh) => "不支持的快照版本 {}，当前版本为 {}",
+            (SnapshotCorrupted, Lang::En) => "snapshot is corrupted: {}",
+            (SnapshotCorrupted, Lang::Zh) => "快照已损坏: {}",
+            (BatchJobPanicked, Lang::En) => "batch job for {} panicked",
+            (BatchJobPanicked, Lang::Zh) => "{} 的批量计算任务异常退出",
+            (InvalidMacdPeriod, Lang::En) => "macd periods must be positive, got {}/{}/{}",
+            (InvalidMacdPeriod, Lang::Zh) => "MACD周期必须为正数，当前为 {}/{}/{}",
+            (UnknownConfigKey, Lang::En) => "unknown config key: {}",
+            (UnknownConfigKey, Lang::Zh) => "未知的配置项: {}",
+            (InvalidConfigValue, Lang::En) => "invalid value for {}: {}",
+            (InvalidConfigValue, Lang::Zh) => "配置项 {} 的取值不合法: {}",
+            (PriceNotOnTick, Lang::En) => "{} price={} is not a multiple of tick_size={}",
+            (PriceNotOnTick, Lang::Zh) => "{} 价格={} 不是最小变动价位 {} 的整数倍",
+            (ReconstructTrimmed, Lang::En) => {
+                "cannot replay history: {} klus were dropped by trim_before"
+            }
+            (ReconstructTrimmed, Lang::Zh) => "前 {} 根KLU已被 trim_before 丢弃，无法重放历史",
+            (TooManyBricks, Lang::En) => {
+                "one klu would produce {} bricks of size {}, more than the limit {}"
+            }
+            (TooManyBricks, Lang::Zh) => "单根KLU会走出 {} 块大小为 {} 的砖，超过上限 {}",
+            (MergeOverlap, Lang::En) => "can't merge klines {} ~ {}: they overlap {} ~ {}",
+            (MergeOverlap, Lang::Zh) => "不能合并 {} ~ {} 的K线，与已有的 {} ~ {} 重叠",
+            (ReconstructRebuilt, Lang::En) => {
+                "cannot replay history: the list was rebuilt by merge or rollback, fx ids would differ"
+            }
+            (ReconstructRebuilt, Lang::Zh) => "列表经过回补合并或回退重算，重放得到的分形编号对不上，无法重放历史",
+            (TickFinerThanPrecision, Lang::En) => {
+                "{}: tick_size={} needs more decimals than price_precision={}"
+            }
+            (TickFinerThanPrecision, Lang::Zh) => "{}: 最小变动价位 {} 的小数位数超过价格精度 {}",
+            (RollbackFailed, Lang::En) => "{}; rolling back also failed: {}",
+            (RollbackFailed, Lang::Zh) => "{}；回退时又出错：{}",
+        }
+    }
+}
+
+/// 按指定语言渲染报错信息，参数不足时对应位置留空
+pub fn render(key: MsgKey, lang: Lang, args: &[&dyn fmt::Display]) -> String {
+    let mut parts = key.template(lang).split("{}");
+    let mut out = parts.next().unwrap_or_default().to_string();
+    for (i, part) in parts.enumerate() {
+        if let Some(arg) = args.get(i) {
+            out.push_str(&arg.to_string());
+        }
+        out.push_str(part);
+    }
+    out
+}
+
+/// 按当前语言渲染报错信息
+pub fn tr(key: MsgKey, args: &[&dyn fmt::Display]) -> String {
+    render(key, lang(), args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let args: [&dyn fmt::Display; 2] = [&"2024/01/02", &3];
+        assert_eq!(
+            render(MsgKey::TooMuchZeroVolume, Lang::En, &args),
+            "2024/01/02 3 consecutive klines have zero volume"
+        );
+        assert_eq!(
+            render(MsgKey::TooMuchZeroVolume, Lang::Zh, &args),
+            "2024/01/02 连续3根K线成交量为0"
+        );
+        assert_eq!(
+            render(MsgKey::CombineTypeUnknown, Lang::En, &[]),
+            "combine type unknown"
+        );
+    }
+}
@@ -0,0 +1,8 @@
+pub mod chan_element;
+pub mod chan_exception;
+pub mod ctime;
+pub mod enums;
+pub mod func_util;
+pub mod messages;
+pub mod profiler;
+pub mod symbol_info;
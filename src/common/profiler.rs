@@ -0,0 +1,75 @@
+use std::time::{Duration, Instant};
+
+/// 每根KLU的计算阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Phase {
+    KlcMerge,
+    FxDetect,
+}
+
+impl Phase {
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::KlcMerge => "klc_merge",
+            Phase::FxDetect => "fx_detect",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PhaseTiming {
+    pub klu_idx: usize,
+    pub phase: Phase,
+    pub elapsed: Duration,
+}
+
+/// 按KLU、按阶段记录耗时，默认不开启
+#[derive(Debug, Clone, Default)]
+pub struct Profiler {
+    records: Vec<PhaseTiming>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, klu_idx: usize, phase: Phase, begin: Instant) {
+        self.records.push(PhaseTiming {
+            klu_idx,
+            phase,
+            elapsed: begin.elapsed(),
+        });
+    }
+
+    pub fn records(&self) -> &[PhaseTiming] {
+        &self.records
+    }
+
+    /// 某阶段的累计耗时
+    pub fn total(&self, phase: Phase) -> Duration {
+        self.records
+            .iter()
+            .filter(|r| r.phase == phase)
+            .map(|r| r.elapsed)
+            .sum()
+    }
+
+    /// 导出为 `klu_idx,phase,elapsed_ns` 三列的CSV文本
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("klu_idx,phase,elapsed_ns");
+        for r in &self.records {
+            out.push_str(&format!(
+                "\n{},{},{}",
+                r.klu_idx,
+                r.phase.name(),
+                r.elapsed.as_nanos()
+            ));
+        }
+        out
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+
+use crate::common::chan_exception::{ChanException, ChanResult, ErrCode};
+use crate::common::messages::{tr, MsgKey};
+
+/// 标的的交易属性：最小变动价位、每手数量、价格精度、币种
+///
+/// 交给 `KLineList::set_symbol_info` 后用于校验KLU价格、导出分形时取整；`round_qty` 供下单时按整手计算
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolInfo {
+    pub code: String,
+    pub tick_size: f64,
+    pub lot_size: u64,
+    pub price_precision: u32,
+    pub currency: String,
+}
+
+impl SymbolInfo {
+    pub fn new(
+        code: &str,
+        tick_size: f64,
+        lot_size: u64,
+        price_precision: u32,
+        currency: &str,
+    ) -> ChanResult<Self> {
+        if tick_size.is_nan() || tick_size <= 0.0 || lot_size == 0 {
+            return Err(ChanException::new(
+                tr(MsgKey::InvalidSymbolInfo, &[&code, &tick_size, &lot_size]),
+                ErrCode::ParaError,
+            ));
+        }
+        if tick_decimals(tick_size).is_none_or(|decimals| decimals > price_precision) {
+            return Err(ChanException::new(
+                tr(
+                    MsgKey::TickFinerThanPrecision,
+                    &[&code, &tick_size, &price_precision],
+                ),
+                ErrCode::ParaError,
+            ));
+        }
+        Ok(Self {
+            code: code.to_string(),
+            tick_size,
+            lot_size,
+            price_precision,
+            currency: currency.to_string(),
+        })
+    }
+
+    /// 按最小变动价位取整，再按价格精度去掉浮点误差；精度不低于最小变动价位的小数位数，结果仍在价位网格上
+    pub fn round_price(&self, price: f64) -> f64 {
+        let ticks = (price / self.tick_size).round();
+        let scale = 10f64.powi(self.price_precision as i32);
+        (ticks * self.tick_size * scale).round() / scale
+    }
+
+    /// 价格为正且落在最小变动价位的整数倍上
+    pub fn is_valid_price(&self, price: f64) -> bool {
+        price > 0.0 && (price - self.round_price(price)).abs() < self.tick_size * 1e-6
+    }
+
+    /// 向下取整到整手
+    pub fn round_qty(&self, qty: f64) -> u64 {
+        if qty <= 0.0 {
+            return 0;
+        }
+        (qty as u64 / self.lot_size) * self.lot_size
+    }
+}
+
+/// 最小变动价位的小数位数，超过 `MAX_DECIMALS` 位（如 1/3）时返回 None
+fn tick_decimals(tick_size: f64) -> Option<u32> {
+    const MAX_DECIMALS: u32 = 15;
+    (0..=MAX_DECIMALS).find(|&decimals| {
+        let scaled = tick_size * 10f64.powi(decimals as i32);
+        (scaled - scaled.round()).abs() < scaled * 1e-9
+    })
+}
+
+/// 按代码登记的 `SymbolInfo`
+#[derive(Debug, Clone, Default)]
+pub struct SymbolRegistry {
+    map: HashMap<String, SymbolInfo>,
+}
+
+impl SymbolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 登记或覆盖，返回被覆盖的旧信息
+    pub fn register(&mut self, info: SymbolInfo) -> Option<SymbolInfo> {
+        self.map.insert(info.code.clone(), info)
+    }
+
+    pub fn get(&self, code: &str) -> Option<&SymbolInfo> {
+        self.map.get(code)
+    }
+
+    pub fn remove(&mut self, code: &str) -> Option<SymbolInfo> {
+        self.map.remove(code)
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symbol_info() {
+        let info = SymbolInfo::new("HK.00700", 0.2, 100, 3, "HKD").unwrap();
+        assert_eq!(info.round_price(350.31), 350.4);
+        assert!(info.is_valid_price(350.4));
+        assert!(!info.is_valid_price(350.3));
+        assert_eq!(info.round_qty(250.0), 200);
+        assert_eq!(info.round_qty(-1.0), 0);
+
+        let err = SymbolInfo::new("X", 0.0, 1, 2, "USD").unwrap_err();
+        assert_eq!(err.errcode, ErrCode::ParaError);
+
+        // 精度比最小变动价位粗时取整会落到网格外
+        let err = SymbolInfo::new("X", 0.005, 1, 2, "USD").unwrap_err();
+        assert_eq!(err.errcode, ErrCode::ParaError);
+        assert!(SymbolInfo::new("X", 1.0 / 3.0, 1, 6, "USD").is_err());
+        let fine = SymbolInfo::new("X", 0.005, 1, 3, "USD").unwrap();
+        assert_eq!(fine.round_price(1.2374), 1.235);
+        assert!(fine.is_valid_price(1.235));
+        assert!(SymbolInfo::new("X", 5.0, 1, 0, "USD").is_ok());
+
+        let mut registry = SymbolRegistry::new();
+        assert!(registry.register(info.clone()).is_none());
+        assert_eq!(registry.get("HK.00700"), Some(&info));
+        assert!(registry.get("US.AAPL").is_none());
+    }
+}
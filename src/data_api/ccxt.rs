@@ -0,0 +1,273 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::common::chan_exception::{ChanException, ChanResult, ErrCode};
+use crate::common::ctime::CTime;
+use crate::common::enums::KLineType;
+use crate::common::func_util::kltype_lt_day;
+use crate::common::messages::{tr, MsgKey};
+use crate::data_api::csv_api::parse_time_column;
+use crate::kline::kline_unit::KLineUnit;
+use crate::kline::trade_info::TradeInfo;
+
+/// ccxt `fetch_ohlcv` 返回的一行：[开盘时间(毫秒), open, high, low, close, volume]
+pub type OhlcvRow = [f64; 6];
+
+/// 交易所的 `fetch_ohlcv` 接口，由调用方用自己的 HTTP 客户端实现
+pub trait OhlcvFetcher {
+    fn fetch_ohlcv(
+        &mut self,
+        symbol: &str,
+        timeframe: &str,
+        since_ms: i64,
+        limit: usize,
+    ) -> ChanResult<Vec<OhlcvRow>>;
+}
+
+/// 与 chan.py 的 CCXT 数据源一致的级别映射
+pub fn ccxt_timeframe(kl_type: KLineType) -> Option<&'static str> {
+    Some(match kl_type {
+        KLineType::KDay => "1d",
+        KLineType::KWeek => "1w",
+        KLineType::KMon => "1M",
+        KLineType::K5M => "5m",
+        KLineType::K15M => "15m",
+        KLineType::K30M => "30m",
+        KLineType::K60M => "1h",
+        _ => return None,
+    })
+}
+
+/// 按页拉取历史K线并转换为KLU，两次请求之间至少间隔 `rate_limit`
+pub struct CcxtLoader<F: OhlcvFetcher> {
+    fetcher: F,
+    symbol: String,
+    kl_type: KLineType,
+    timeframe: &'static str,
+    page_size: usize,
+    rate_limit: Duration,
+    last_request: Option<Instant>,
+}
+
+impl<F: OhlcvFetcher> CcxtLoader<F> {
+    pub fn new(fetcher: F, symbol: &str, kl_type: KLineType) -> ChanResult<Self> {
+        let timeframe = ccxt_timeframe(kl_type).ok_or_else(|| {
+            ChanException::new(
+                tr(
+                    MsgKey::KlTypeNotSupported,
+                    &[&"ccxt", &format!("{kl_type:?}")],
+                ),
+                ErrCode::ParaError,
+            )
+        })?;
+        Ok(Self {
+            fetcher,
+            symbol: symbol.to_string(),
+            kl_type,
+            timeframe,
+            page_size: 500,
+            rate_limit: Duration::from_millis(50),
+            last_request: None,
+        })
+    }
+
+    /// 每页条数，默认500
+    pub fn page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    /// 两次请求的最小间隔，默认50ms（binance 的 rateLimit）
+    pub fn rate_limit(mut self, rate_limit: Duration) -> Self {
+        self.rate_limit = rate_limit;
+        self
+    }
+
+    /// 拉取 [begin_date, end_date] 内的K线，日期格式同 `parse_time_column`，按 UTC 解释
+    ///
+    /// 时间取交易所返回的开盘时间，天级别及以上同 chan.py 按自适应处理（auto）；价格不合法时自动修正。
+    /// 每页之后从该页最后一根的下一毫秒继续，某页没有新数据或已越过 `end_date` 时停止
+    pub fn load(&mut self, begin_date: &str, end_date: Option<&str>) -> ChanResult<Vec<KLineUnit>> {
+        let mut since_ms = parse_time_column(begin_date)?.ts() * 1000;
+        let end_ms = match end_date {
+            Some(end) => Some(parse_time_column(end)?.ts() * 1000),
+            None => None,
+        };
+        let auto = !kltype_lt_day(self.kl_type);
+        let mut klus = Vec::new();
+        loop {
+            self.wait_rate_limit();
+            let rows =
+                self.fetcher
+                    .fetch_ohlcv(&self.symbol, self.timeframe, since_ms, self.page_size)?;
+            let page_len = rows.len();
+            let Some(last_ts) = rows.iter().map(|row| row[0] as i64).max() else {
+                break;
+            };
+            let page_begin = klus.len();
+            for [ts_ms, open, high, low, close, volume] in rows {
+                let ts_ms = ts_ms as i64;
+                if ts_ms < since_ms || end_ms.is_some_and(|end| ts_ms > end) {
+                    continue;
+                }
+                let trade_info = TradeInfo {
+                    volume: Some(volume),
+                    ..Default::default()
+                };
+                let t = CTime::from_ts(ts_ms.div_euclid(1000));
+                let time = CTime::new(t.year, t.month, t.day, t.hour, t.minute, t.second, auto);
+                let mut klu = KLineUnit::new(time, open, high, low, close, trade_info, true)?;
+                klu.kl_type = Some(self.kl_type);
+                klus.push(klu);
+            }
+            let no_new_rows = klus.len() == page_begin;
+            let reached_end = end_ms.is_some_and(|end| last_ts >= end);
+            since_ms = since_ms.max(last_ts + 1);
+            if page_len < self.page_size || no_new_rows || reached_end {
+                break;
+            }
+        }
+        Ok(klus)
+    }
+
+    fn wait_rate_limit(&mut self) {
+        if let Some(last) = self.last_request {
+            let elapsed = last.elapsed();
+            if elapsed < self.rate_limit {
+                thread::sleep(self.rate_limit - elapsed);
+            }
+        }
+        self.last_request = Some(Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 每天一根、从 2024-01-01 开始的假数据
+    struct FakeExchange {
+        rows: Vec<OhlcvRow>,
+        calls: Vec<(i64, usize)>,
+    }
+
+    impl OhlcvFetcher for FakeExchange {
+        fn fetch_ohlcv(
+            &mut self,
+            _symbol: &str,
+            timeframe: &str,
+            since_ms: i64,
+            limit: usize,
+        ) -> ChanResult<Vec<OhlcvRow>> {
+            assert_eq!(timeframe, "1d");
+            self.calls.push((since_ms, limit));
+            Ok(self
+                .rows
+                .iter()
+                .filter(|row| row[0] as i64 >= since_ms)
+                .take(limit)
+                .copied()
+                .collect())
+        }
+    }
+
+    #[test]
+    fn test_load() {
+        let day0 = CTime::new(2024, 1, 1, 0, 0, 0, false).ts() * 1000;
+        let rows = (0..7)
+            .map(|i| {
+                let p = 100.0 + i as f64;
+                [(day0 + i * 86_400_000) as f64, p, p + 1.0, p - 1.0, p, 10.0]
+            })
+            .collect();
+        let fetcher = FakeExchange {
+            rows,
+            calls: Vec::new(),
+        };
+        let mut loader = CcxtLoader::new(fetcher, "BTC/USDT", KLineType::KDay)
+            .unwrap()
+            .page_size(2)
+            .rate_limit(Duration::ZERO);
+        let klus = loader.load("2024-01-02", Some("2024-01-06")).unwrap();
+        assert_eq!(klus.len(), 5);
+        assert_eq!(klus[0].time, CTime::new(2024, 1, 2, 0, 0, 0, true));
+        assert_eq!(klus[4].close, 105.0);
+        assert_eq!(klus[0].kl_type, Some(KLineType::KDay));
+        assert_eq!(loader.fetcher.calls.len(), 3);
+
+        let err = CcxtLoader::new(
+            FakeExchange {
+                rows: Vec::new(),
+                calls: Vec::new(),
+            },
+            "BTC/USDT",
+            KLineType::K1M,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(err.errcode, ErrCode::ParaError);
+    }
+
+    /// 不理会 `since_ms`，每次都返回同一整页
+    struct StuckExchange {
+        rows: Vec<OhlcvRow>,
+        calls: usize,
+    }
+
+    impl OhlcvFetcher for StuckExchange {
+        fn fetch_ohlcv(
+            &mut self,
+            _symbol: &str,
+            _timeframe: &str,
+            _since_ms: i64,
+            _limit: usize,
+        ) -> ChanResult<Vec<OhlcvRow>> {
+            self.calls += 1;
+            assert!(self.calls < 10, "loader does not terminate");
+            Ok(self.rows.clone())
+        }
+    }
+
+    #[test]
+    fn test_load_stuck_page() {
+        let day0 = CTime::new(2024, 1, 1, 0, 0, 0, false).ts() * 1000;
+        let page = |first: i64| -> Vec<OhlcvRow> {
+            (first..first + 3)
+                .map(|i| [(day0 + i * 86_400_000) as f64, 10.0, 11.0, 9.0, 10.0, 1.0])
+                .collect()
+        };
+
+        // 整页都在 end_date 之后
+        let mut loader = CcxtLoader::new(
+            StuckExchange {
+                rows: page(30),
+                calls: 0,
+            },
+            "BTC/USDT",
+            KLineType::KDay,
+        )
+        .unwrap()
+        .page_size(3)
+        .rate_limit(Duration::ZERO);
+        assert!(loader
+            .load("2024-01-01", Some("2024-01-10"))
+            .unwrap()
+            .is_empty());
+        assert_eq!(loader.fetcher.calls, 1);
+
+        // 第二页和第一页重复，没有新数据
+        let mut loader = CcxtLoader::new(
+            StuckExchange {
+                rows: page(0),
+                calls: 0,
+            },
+            "BTC/USDT",
+            KLineType::KDay,
+        )
+        .unwrap()
+        .page_size(3)
+        .rate_limit(Duration::ZERO);
+        assert_eq!(loader.load("2024-01-01", None).unwrap().len(), 3);
+        assert_eq!(loader.fetcher.calls, 2);
+    }
+}
@@ -0,0 +1,160 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::common::chan_exception::{ChanException, ChanResult, ErrCode};
+use crate::common::ctime::CTime;
+use crate::common::enums::KLineType;
+use crate::common::messages::{tr, MsgKey};
+use crate::kline::kline_unit::KLineUnit;
+use crate::kline::trade_info::TradeInfo;
+
+/// 解析csv中的时间列，支持 `2021-09-13`、`20210902113000000`、`2021-09-13 11:30:00` 三种格式
+pub fn parse_time_column(inp: &str) -> ChanResult<CTime> {
+    let num = |range: std::ops::Range<usize>| {
+        inp.get(range)
+            .and_then(|s| s.parse::<u32>().ok())
+            .ok_or_else(|| {
+                ChanException::new(
+                    tr(MsgKey::UnknownTimeFormat, &[&inp]),
+                    ErrCode::SrcDataFormatError,
+                )
+            })
+    };
+    let (year, month, day, hour, minute) = match inp.len() {
+        10 => (num(0..4)?, num(5..7)?, num(8..10)?, 0, 0),
+        17 => (
+            num(0..4)?,
+            num(4..6)?,
+            num(6..8)?,
+            num(8..10)?,
+            num(10..12)?,
+        ),
+        19 => (
+            num(0..4)?,
+            num(5..7)?,
+            num(8..10)?,
+            num(11..13)?,
+            num(14..16)?,
+        ),
+        _ => {
+            return Err(ChanException::new(
+                tr(MsgKey::UnknownTimeFormat, &[&inp]),
+                ErrCode::SrcDataFormatError,
+            ))
+        }
+    };
+    Ok(CTime::new(year as i32, month, day, hour, minute, 0, false))
+}
+
+/// 读取 `time_key,open,high,low,close,volume` 六列的csv，`headers_exist` 为 true 时跳过第一行
+///
+/// `begin_date`/`end_date` 按时间列的原始字符串比较，与 chan.py 的 CSV_API 一致
+pub fn load_csv(
+    path: &Path,
+    kl_type: Option<KLineType>,
+    headers_exist: bool,
+    begin_date: Option<&str>,
+    end_date: Option<&str>,
+) -> ChanResult<Vec<KLineUnit>> {
+    let file = File::open(path).map_err(|_| {
+        ChanException::new(
+            tr(MsgKey::FileNotExist, &[&path.display()]),
+            ErrCode::SrcDataNotFound,
+        )
+    })?;
+    let format_err = |line_number: usize| {
+        ChanException::new(
+            tr(
+                MsgKey::FileFormatError,
+                &[&path.display(), &(line_number + 1)],
+            ),
+            ErrCode::SrcDataFormatError,
+        )
+    };
+    let mut klus = Vec::new();
+    for (line_number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|_| format_err(line_number))?;
+        if (headers_exist && line_number == 0) || line.trim().is_empty() {
+            continue;
+        }
+        let data: Vec<&str> = line.trim_end_matches('\r').split(',').collect();
+        if data.len() != 6 {
+            return Err(format_err(line_number));
+        }
+        if begin_date.is_some_and(|begin| data[0] < begin)
+            || end_date.is_some_and(|end| data[0] > end)
+        {
+            continue;
+        }
+        let mut values = [0.0; 5];
+        for (value, s) in values.iter_mut().zip(&data[1..]) {
+            *value = s.trim().parse().map_err(|_| format_err(line_number))?;
+        }
+        let [open, high, low, close, volume] = values;
+        let trade_info = TradeInfo {
+            volume: Some(volume),
+            ..Default::default()
+        };
+        let mut klu = KLineUnit::new(
+            parse_time_column(data[0])?,
+            open,
+            high,
+            low,
+            close,
+            trade_info,
+            false,
+        )?;
+        klu.kl_type = kl_type;
+        klus.push(klu);
+    }
+    Ok(klus)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_time_column() {
+        assert_eq!(
+            parse_time_column("2021-09-13").unwrap(),
+            CTime::new(2021, 9, 13, 0, 0, 0, false)
+        );
+        assert_eq!(
+            parse_time_column("20210902113000000").unwrap(),
+            CTime::new(2021, 9, 2, 11, 30, 0, false)
+        );
+        assert_eq!(
+            parse_time_column("2021-09-13 11:30:00").unwrap(),
+            CTime::new(2021, 9, 13, 11, 30, 0, false)
+        );
+        let err = parse_time_column("2021/9/13").unwrap_err();
+        assert_eq!(err.errcode, ErrCode::SrcDataFormatError);
+    }
+
+    #[test]
+    fn test_load_csv() {
+        let path = std::env::temp_dir().join(format!("chan_csv_api_{}.csv", std::process::id()));
+        std::fs::write(
+            &path,
+            "time_key,open,high,low,close,volume\n\
+             2024-01-02,9,10,8,9.5,100\n\
+             2024-01-03,9.5,11,9,10.5,200\n\
+             2024-01-04,10.5,11.5,10,11,300\n",
+        )
+        .unwrap();
+        let klus = load_csv(&path, Some(KLineType::KDay), true, Some("2024-01-03"), None).unwrap();
+        assert_eq!(klus.len(), 2);
+        assert_eq!(klus[0].high, 11.0);
+        assert_eq!(klus[1].trade_info.volume, Some(300.0));
+
+        std::fs::write(&path, "2024-01-02,9,10,8\n").unwrap();
+        let err = load_csv(&path, None, false, None, None).unwrap_err();
+        assert_eq!(err.errcode, ErrCode::SrcDataFormatError);
+        std::fs::remove_file(&path).unwrap();
+
+        let err = load_csv(&path, None, false, None, None).unwrap_err();
+        assert_eq!(err.errcode, ErrCode::SrcDataNotFound);
+    }
+}
@@ -0,0 +1,4 @@
+//! 各数据源的K线加载器，对应 chan.py 的 `DataAPI` 目录：本地CSV、CCXT交易所接口等并列放在这里
+
+pub mod ccxt;
+pub mod csv_api;
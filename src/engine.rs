@@ -0,0 +1,197 @@
+//! 逐根推入KLU并以事件形式通知结构变化，适合实时场景
+//!
+//! 目前只有KLC和分形两级，笔、线段、中枢、买卖点移植后在 `ChanEvent` 中补充对应事件
+
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use crate::common::chan_exception::ChanResult;
+use crate::kline::fx_list::Fx;
+use crate::kline::kline_list::KLineList;
+use crate::kline::kline_unit::KLineUnit;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChanEvent {
+    /// 新开出一根KLC
+    NewKlc { klc_idx: usize },
+    /// 出现新分形，此时右侧KLC还没走完
+    NewFx(Fx),
+    /// 分形确定，之后不会再变
+    FxConfirmed(Fx),
+    /// 未确定的分形因最后一根KLC变化而消失
+    FxRemoved { id: u64 },
+}
+
+type Callback = Box<dyn FnMut(&ChanEvent) + Send>;
+
+pub struct ChanEngine {
+    kl_list: KLineList,
+    callbacks: Vec<Callback>,
+    senders: Vec<Sender<ChanEvent>>,
+}
+
+impl ChanEngine {
+    pub fn new(kl_list: KLineList) -> Self {
+        Self {
+            kl_list,
+            callbacks: Vec::new(),
+            senders: Vec::new(),
+        }
+    }
+
+    /// 注册回调，每个事件按发生顺序调用一次
+    pub fn on_event(&mut self, callback: impl FnMut(&ChanEvent) + Send + 'static) {
+        self.callbacks.push(Box::new(callback));
+    }
+
+    /// 以通道方式订阅事件，接收端丢弃后自动退订
+    pub fn subscribe(&mut self) -> Receiver<ChanEvent> {
+        let (tx, rx) = mpsc::channel();
+        self.senders.push(tx);
+        rx
+    }
+
+    pub fn kl_list(&self) -> &KLineList {
+        &self.kl_list
+    }
+
+    pub fn into_inner(self) -> KLineList {
+        self.kl_list
+    }
+
+    /// 推入一根KLU，比较前后状态后派发事件，出错时不派发
+    pub fn add_klu(&mut self, klu: KLineUnit) -> ChanResult<()> {
+        let klc_cnt = self.kl_list.len();
+        let next_id = self.kl_list.fx_list().next_id();
+        // 已确定的分形不会再变，只需看列表尾部
+        let mut unsure: Vec<u64> = self
+            .kl_list
+            .fx_list()
+            .iter()
+            .rev()
+            .take_while(|fx| !fx.is_sure)
+            .map(|fx| fx.id)
+            .collect();
+        unsure.reverse();
+
+        self.kl_list.add_single_klu(klu)?;
+
+        let mut events: Vec<ChanEvent> = (klc_cnt..self.kl_list.len())
+            .map(|klc_idx| ChanEvent::NewKlc { klc_idx })
+            .collect();
+        let fx_list = self.kl_list.fx_list();
+        let mut new_fx: Vec<&Fx> = fx_list
+            .iter()
+            .rev()
+            .take_while(|fx| fx.id >= next_id)
+            .collect();
+        new_fx.reverse();
+        let mut confirmed = Vec::new();
+        for &id in &unsure {
+            match fx_list.get_by_id(id) {
+                None => events.push(ChanEvent::FxRemoved { id }),
+                Some(fx) if fx.is_sure => confirmed.push(fx),
+                Some(_) => {}
+            }
+        }
+        confirmed.extend(new_fx.iter().copied().filter(|fx| fx.is_sure));
+        events.extend(new_fx.into_iter().map(|fx| ChanEvent::NewFx(fx.clone())));
+        events.extend(
+            confirmed
+                .into_iter()
+                .map(|fx| ChanEvent::FxConfirmed(fx.clone())),
+        );
+        self.dispatch(events);
+        Ok(())
+    }
+
+    fn dispatch(&mut self, events: Vec<ChanEvent>) {
+        for event in &events {
+            for callback in &mut self.callbacks {
+                callback(event);
+            }
+        }
+        self.senders
+            .retain(|tx| events.iter().all(|event| tx.send(event.clone()).is_ok()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::chan_config::{ChanConfig, CombineDirPolicy};
+    use crate::common::ctime::CTime;
+    use crate::common::enums::KLineType;
+    use crate::kline::trade_info::TradeInfo;
+    use crate::testkit::MarketGen;
+
+    #[test]
+    fn test_events() {
+        let series = MarketGen::new(CTime::new(2024, 1, 1, 0, 0, 0, false), 10.0)
+            .trend(3, 2.0, 1.0, 4)
+            .build()
+            .unwrap();
+        let mut engine = ChanEngine::new(KLineList::new(Some(KLineType::KDay)));
+        let rx = engine.subscribe();
+        let klc_cnt = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&klc_cnt);
+        engine.on_event(move |event| {
+            if let ChanEvent::NewKlc { .. } = event {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+        for klu in series.klus {
+            engine.add_klu(klu).unwrap();
+        }
+
+        let events: Vec<ChanEvent> = rx.try_iter().collect();
+        assert_eq!(klc_cnt.load(Ordering::Relaxed), engine.kl_list().len());
+        let new_ids: Vec<u64> = events
+            .iter()
+            .filter_map(|e| match e {
+                ChanEvent::NewFx(fx) => Some(fx.id),
+                _ => None,
+            })
+            .collect();
+        let sure_ids: Vec<u64> = events
+            .iter()
+            .filter_map(|e| match e {
+                ChanEvent::FxConfirmed(fx) => Some(fx.id),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(new_ids.len(), engine.kl_list().fx_list().len());
+        let sure_cnt = engine.kl_list().fx_list().iter().filter(|fx| fx.is_sure);
+        assert_eq!(sure_ids.len(), sure_cnt.count());
+        assert!(sure_ids.iter().all(|id| new_ids.contains(id)));
+    }
+
+    #[test]
+    fn test_fx_removed() {
+        let config = ChanConfig {
+            combine_dir: CombineDirPolicy::Close,
+            ..Default::default()
+        };
+        let mut engine = ChanEngine::new(KLineList::with_config(Some(KLineType::KDay), config));
+        let rx = engine.subscribe();
+        for (day, high, low) in [
+            (1, 10.0, 8.0),
+            (2, 12.0, 9.0),
+            (3, 11.0, 7.0),
+            (4, 13.0, 6.0),
+        ] {
+            let t = CTime::new(2024, 1, day, 0, 0, 0, false);
+            let klu = KLineUnit::new(t, low, high, low, high, TradeInfo::default(), false);
+            engine.add_klu(klu.unwrap()).unwrap();
+        }
+        // 收阳的第4根被包含后按高高合并，顶分型被破坏
+        let events: Vec<ChanEvent> = rx.try_iter().collect();
+        assert!(matches!(
+            events.last(),
+            Some(ChanEvent::FxRemoved { id: 0 })
+        ));
+        assert!(engine.kl_list().fx_list().is_empty());
+    }
+}
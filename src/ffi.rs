@@ -0,0 +1,191 @@
+//! C 接口，编译为 cdylib 后供 C/C++/C#/Go 等调用
+//!
+//! 用法：`chan_analyzer_new` 创建 → 逐根 `chan_analyzer_feed_bar` → `chan_analyzer_poll_fx`
+//! 取出新确定的分形 → `chan_analyzer_free` 释放。除 `chan_analyzer_free` 外指针参数都不能为空。
+
+use crate::common::chan_exception::ErrCode;
+use crate::common::ctime::CTime;
+use crate::common::enums::{FxType, KLineType};
+use crate::kline::kline_list::KLineList;
+use crate::kline::kline_unit::KLineUnit;
+use crate::kline::trade_info::TradeInfo;
+
+pub struct ChanAnalyzer {
+    kl_list: KLineList,
+    /// 下一个待上报的分形编号
+    next_fx_id: u64,
+}
+
+/// 一根K线，时间为该K线的结束时间
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ChanBar {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    /// 小于0表示没有成交量数据
+    pub volume: f64,
+}
+
+/// 已确定的分形，fx_type 为 1 表示顶分型，-1 表示底分型
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChanFxEvent {
+    pub id: u64,
+    pub fx_type: i32,
+    pub klc_idx: u64,
+    pub begin_ts: i64,
+    pub end_ts: i64,
+    pub high: f64,
+    pub low: f64,
+    pub gap_with_pre: bool,
+    pub gap_with_next: bool,
+}
+
+/// 创建分析器，`kl_type` 取值同 `KLineType`（如日线为15），不认识的取值返回空指针
+#[no_mangle]
+pub extern "C" fn chan_analyzer_new(kl_type: i32) -> *mut ChanAnalyzer {
+    let Some(kl_type) = KLineType::from_value(kl_type) else {
+        return std::ptr::null_mut();
+    };
+    Box::into_raw(Box::new(ChanAnalyzer {
+        kl_list: KLineList::new(Some(kl_type)),
+        next_fx_id: 0,
+    }))
+}
+
+/// 追加一根K线，成功返回0，失败返回 `ErrCode` 的取值
+///
+/// # Safety
+/// `analyzer` 必须是 `chan_analyzer_new` 返回且尚未释放的指针，`bar` 必须指向有效的 `ChanBar`
+#[no_mangle]
+pub unsafe extern "C" fn chan_analyzer_feed_bar(
+    analyzer: *mut ChanAnalyzer,
+    bar: *const ChanBar,
+) -> i32 {
+    let (analyzer, bar) = (&mut *analyzer, &*bar);
+    let time = CTime::new(
+        bar.year, bar.month, bar.day, bar.hour, bar.minute, bar.second, false,
+    );
+    let trade_info = TradeInfo {
+        volume: (bar.volume >= 0.0).then_some(bar.volume),
+        ..Default::default()
+    };
+    let result = KLineUnit::new(
+        time, bar.open, bar.high, bar.low, bar.close, trade_info, false,
+    )
+    .and_then(|klu| analyzer.kl_list.add_single_klu(klu));
+    match result {
+        Ok(()) => 0,
+        Err(e) => e.errcode as i32,
+    }
+}
+
+/// 取出一个尚未上报过的已确定分形，写入 `out` 后返回1，没有时返回0
+///
+/// # Safety
+/// `analyzer` 必须是 `chan_analyzer_new` 返回且尚未释放的指针，`out` 必须可写
+#[no_mangle]
+pub unsafe extern "C" fn chan_analyzer_poll_fx(
+    analyzer: *mut ChanAnalyzer,
+    out: *mut ChanFxEvent,
+) -> i32 {
+    let analyzer = &mut *analyzer;
+    let fx_list = analyzer.kl_list.fx_list();
+    let Some(fx) = fx_list
+        .iter()
+        .find(|fx| fx.id >= analyzer.next_fx_id)
+        .filter(|fx| fx.is_sure)
+    else {
+        return 0;
+    };
+    *out = ChanFxEvent {
+        id: fx.id,
+        fx_type: match fx.fx_type {
+            FxType::Top => 1,
+            FxType::Bottom => -1,
+            FxType::Unknown => 0,
+        },
+        klc_idx: fx.klc_idx as u64,
+        begin_ts: fx.time_begin.ts(),
+        end_ts: fx.time_end.ts(),
+        high: fx.high,
+        low: fx.low,
+        gap_with_pre: fx.gap.with_pre,
+        gap_with_next: fx.gap.with_next,
+    };
+    analyzer.next_fx_id = fx.id + 1;
+    1
+}
+
+/// 释放分析器，传入空指针时什么都不做
+///
+/// # Safety
+/// `analyzer` 必须是 `chan_analyzer_new` 返回的指针，且只能释放一次
+#[no_mangle]
+pub unsafe extern "C" fn chan_analyzer_free(analyzer: *mut ChanAnalyzer) {
+    if !analyzer.is_null() {
+        drop(Box::from_raw(analyzer));
+    }
+}
+
+/// 判断 `chan_analyzer_feed_bar` 返回的错误码是否属于K线数据错误
+#[no_mangle]
+pub extern "C" fn chan_errcode_is_kldata_err(code: i32) -> bool {
+    (ErrCode::KlErrBegin as i32) < code && code < ErrCode::KlErrEnd as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bar(day: u32, high: f64, low: f64) -> ChanBar {
+        ChanBar {
+            year: 2024,
+            month: 1,
+            day,
+            hour: 0,
+            minute: 0,
+            second: 0,
+            open: low,
+            high,
+            low,
+            close: high,
+            volume: -1.0,
+        }
+    }
+
+    #[test]
+    fn test_feed_and_poll() {
+        assert!(chan_analyzer_new(99).is_null());
+        let analyzer = chan_analyzer_new(KLineType::KDay as i32);
+        let mut event = ChanFxEvent::default();
+        unsafe {
+            for (i, &(high, low)) in [(10.0, 8.0), (12.0, 9.0), (11.0, 7.0), (13.0, 10.0)]
+                .iter()
+                .enumerate()
+            {
+                assert_eq!(
+                    chan_analyzer_feed_bar(analyzer, &bar(1 + i as u32, high, low)),
+                    0
+                );
+            }
+            // 第1根KLC的顶分型已确定，第2根的底分型右侧KLC还没走完
+            assert_eq!(chan_analyzer_poll_fx(analyzer, &mut event), 1);
+            assert_eq!((event.id, event.fx_type, event.klc_idx), (0, 1, 1));
+            assert_eq!(chan_analyzer_poll_fx(analyzer, &mut event), 0);
+
+            let code = chan_analyzer_feed_bar(analyzer, &bar(5, 9.0, 10.0));
+            assert_eq!(code, ErrCode::KlDataInvalid as i32);
+            assert!(chan_errcode_is_kldata_err(code));
+            chan_analyzer_free(analyzer);
+        }
+    }
+}
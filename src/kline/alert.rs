@@ -0,0 +1,110 @@
+use crate::common::ctime::CTime;
+use crate::common::enums::FxType;
+use crate::kline::fx_list::FxList;
+use crate::kline::kline_unit::KLineUnit;
+
+/// 价格提醒规则，均以收盘价从一侧穿越到另一侧为触发条件
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlertRule {
+    /// 收盘价上穿给定价位
+    PriceAbove(f64),
+    /// 收盘价下穿给定价位
+    PriceBelow(f64),
+    /// 收盘价上穿最近一个顶分型的高点
+    BreakLastTopFx,
+    /// 收盘价下穿最近一个底分型的低点
+    BreakLastBottomFx,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlertEvent {
+    /// `AlertEngine::add_rule` 返回的编号
+    pub rule_id: usize,
+    pub rule: AlertRule,
+    pub klu_idx: usize,
+    pub time: CTime,
+    /// 被穿越的价位
+    pub level: f64,
+    pub close: f64,
+}
+
+/// 每根KLU进来后检查所有规则，触发的提醒暂存起来等调用方取走
+#[derive(Debug, Clone, Default)]
+pub struct AlertEngine {
+    rules: Vec<Option<AlertRule>>,
+    events: Vec<AlertEvent>,
+}
+
+impl AlertEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_rule(&mut self, rule: AlertRule) -> usize {
+        self.rules.push(Some(rule));
+        self.rules.len() - 1
+    }
+
+    /// 删除规则，编号不会被复用
+    pub fn remove_rule(&mut self, rule_id: usize) -> Option<AlertRule> {
+        self.rules.get_mut(rule_id)?.take()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.iter().all(Option::is_none)
+    }
+
+    /// 取走所有已触发的提醒
+    pub fn drain_events(&mut self) -> Vec<AlertEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// `pre_close` 为上一根KLU的收盘价，没有上一根时不触发
+    pub fn evaluate(&mut self, pre_close: Option<f64>, klu: &KLineUnit, fx_list: &FxList) {
+        let Some(pre_close) = pre_close else {
+            return;
+        };
+        let last_fx_val = |fx_type: FxType| {
+            fx_list
+                .iter()
+                .rev()
+                .find(|fx| fx.fx_type == fx_type)
+                .map(|fx| {
+                    if fx_type == FxType::Top {
+                        fx.high
+                    } else {
+                        fx.low
+                    }
+                })
+        };
+        for (rule_id, rule) in self.rules.iter().enumerate() {
+            let Some(rule) = *rule else {
+                continue;
+            };
+            let (level, up) = match rule {
+                AlertRule::PriceAbove(level) => (Some(level), true),
+                AlertRule::PriceBelow(level) => (Some(level), false),
+                AlertRule::BreakLastTopFx => (last_fx_val(FxType::Top), true),
+                AlertRule::BreakLastBottomFx => (last_fx_val(FxType::Bottom), false),
+            };
+            let Some(level) = level else {
+                continue;
+            };
+            let crossed = if up {
+                pre_close <= level && klu.close > level
+            } else {
+                pre_close >= level && klu.close < level
+            };
+            if crossed {
+                self.events.push(AlertEvent {
+                    rule_id,
+                    rule,
+                    klu_idx: klu.idx(),
+                    time: klu.time,
+                    level,
+                    close: klu.close,
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,391 @@
+use crate::common::chan_exception::{ChanException, ChanResult, ErrCode};
+use crate::common::ctime::CTime;
+use crate::common::enums::KLineType;
+use crate::common::messages::{tr, MsgKey};
+use crate::kline::kline_unit::KLineUnit;
+use crate::kline::trade_info::TradeInfo;
+
+/// 单根KLU最多走出的砖块数，砖块相对跳空过小时报错而不是无限产出
+const MAX_BRICKS_PER_KLU: usize = 1000;
+
+/// 砖块/区间大小
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BarSize {
+    Fixed(f64),
+    /// 前 `period` 根输入K线的 ATR 乘以 `multiplier`，预热结束后固定不变，避免砖块网格漂移；
+    /// 这几根全是一字线、ATR 为0时继续往后取最近 `period` 根，直到 ATR 为正
+    Atr {
+        period: usize,
+        multiplier: f64,
+    },
+}
+
+/// 与时间无关的K线合成方式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BarMode {
+    /// 只看收盘价，同向延续需再走一块砖，反转需走两块砖
+    Renko(BarSize),
+    /// 高低点区间达到大小即收线
+    Range(BarSize),
+    /// 累计成交量达到阈值即收线
+    Volume(f64),
+    /// 累计成交额达到阈值即收线，没有成交额时按收盘价乘成交量估算
+    Dollar(f64),
+}
+
+impl BarMode {
+    fn bar_size(&self) -> BarSize {
+        match *self {
+            BarMode::Renko(size) | BarMode::Range(size) => size,
+            BarMode::Volume(v) | BarMode::Dollar(v) => BarSize::Fixed(v),
+        }
+    }
+}
+
+/// 正在合成中的K线
+#[derive(Debug, Clone)]
+struct PendingBar {
+    time: CTime,
+    open: f64,
+    high: f64,
+    low: f64,
+    close: f64,
+    volume: Option<f64>,
+    turnover: Option<f64>,
+}
+
+impl PendingBar {
+    fn new(klu: &KLineUnit) -> Self {
+        Self {
+            time: klu.time,
+            open: klu.open,
+            high: klu.high,
+            low: klu.low,
+            close: klu.close,
+            volume: klu.trade_info.volume,
+            turnover: klu.trade_info.turnover,
+        }
+    }
+
+    fn merge(&mut self, klu: &KLineUnit) {
+        self.time = klu.time;
+        self.high = self.high.max(klu.high);
+        self.low = self.low.min(klu.low);
+        self.close = klu.close;
+        self.volume = add_opt(self.volume, klu.trade_info.volume);
+        self.turnover = add_opt(self.turnover, klu.trade_info.turnover);
+    }
+}
+
+fn add_opt(a: Option<f64>, b: Option<f64>) -> Option<f64> {
+    match (a, b) {
+        (None, None) => None,
+        _ => Some(a.unwrap_or(0.0) + b.unwrap_or(0.0)),
+    }
+}
+
+/// 把输入的KLU流合成为 renko/range 等K线，产出的KLU可直接喂给 `KLineList`
+#[derive(Debug, Clone)]
+pub struct BarBuilder {
+    mode: BarMode,
+    size: Option<f64>,
+    tr_lst: Vec<f64>,
+    pre_close: Option<f64>,
+    last_ts: Option<i64>,
+    kl_type: Option<KLineType>,
+    brick: Option<(f64, f64)>, // renko: 上一块砖的(低, 高)，首块砖之前为基准价
+    pending: Option<PendingBar>,
+    amount: f64, // volume/dollar: 未收线部分的累计量
+}
+
+impl BarBuilder {
+    pub fn new(mode: BarMode) -> ChanResult<Self> {
+        let size = mode.bar_size();
+        let (fixed, bad) = match size {
+            BarSize::Fixed(v) => (Some(v), v.is_nan() || v <= 0.0),
+            BarSize::Atr { period, multiplier } => (
+                None,
+                period == 0 || multiplier.is_nan() || multiplier <= 0.0,
+            ),
+        };
+        if bad {
+            return Err(ChanException::new(
+                tr(MsgKey::InvalidBarSize, &[&format!("{size:?}")]),
+                ErrCode::ParaError,
+            ));
+        }
+        Ok(Self {
+            mode,
+            size: fixed,
+            tr_lst: Vec::new(),
+            pre_close: None,
+            last_ts: None,
+            kl_type: None,
+            brick: None,
+            pending: None,
+            amount: 0.0,
+        })
+    }
+
+    /// 当前砖块/区间大小，ATR 模式预热结束前为 None
+    pub fn size(&self) -> Option<f64> {
+        self.size
+    }
+
+    /// 输入一根KLU，返回因此收线的合成K线
+    pub fn push(&mut self, klu: &KLineUnit) -> ChanResult<Vec<KLineUnit>> {
+        self.kl_type = klu.kl_type;
+        if self.size.is_none() {
+            self.warm_up(klu);
+            return Ok(Vec::new());
+        }
+        let size = self.size.expect("size is set after warm up");
+        match self.mode {
+            BarMode::Renko(_) => self.push_renko(klu, size),
+            BarMode::Range(_) => self.push_range(klu, size),
+            BarMode::Volume(_) | BarMode::Dollar(_) => self.push_amount(klu, size),
+        }
+    }
+
+    /// 取出尚未收线的K线，renko 模式下没有这种K线
+    pub fn flush(&mut self) -> ChanResult<Option<KLineUnit>> {
+        self.amount = 0.0;
+        match self.pending.take() {
+            Some(bar) => self.emit(bar).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// 累计最近 `period` 根的 ATR，预热期的KLU不参与合成
+    fn warm_up(&mut self, klu: &KLineUnit) {
+        let BarSize::Atr { period, multiplier } = self.mode.bar_size() else {
+            unreachable!("fixed size is set in new")
+        };
+        let mut tr = klu.high - klu.low;
+        if let Some(pre_close) = self.pre_close {
+            tr = tr
+                .max((klu.high - pre_close).abs())
+                .max((klu.low - pre_close).abs());
+        }
+        self.pre_close = Some(klu.close);
+        self.tr_lst.push(tr);
+        if self.tr_lst.len() > period {
+            self.tr_lst.remove(0);
+        }
+        if self.tr_lst.len() < period {
+            return;
+        }
+        let size = self.tr_lst.iter().sum::<f64>() / period as f64 * multiplier;
+        if !(size > 0.0 && size.is_finite()) {
+            return;
+        }
+        self.size = Some(size);
+        // 预热最后一根只作为 renko 的基准价
+        if let BarMode::Renko(_) = self.mode {
+            self.brick = Some((klu.close, klu.close));
+        }
+    }
+
+    fn push_renko(&mut self, klu: &KLineUnit, size: f64) -> ChanResult<Vec<KLineUnit>> {
+        let Some((mut low, mut high)) = self.brick else {
+            self.brick = Some((klu.close, klu.close));
+            return Ok(Vec::new());
+        };
+        let brick_cnt = ((klu.close - high).max(low - klu.close) / size).floor();
+        if brick_cnt > MAX_BRICKS_PER_KLU as f64 {
+            return Err(ChanException::new(
+                tr(
+                    MsgKey::TooManyBricks,
+                    &[&brick_cnt, &size, &MAX_BRICKS_PER_KLU],
+                ),
+                ErrCode::ParaError,
+            ));
+        }
+        let mut res = Vec::new();
+        let mut trade_info = klu.trade_info.clone();
+        loop {
+            let (open, close) = if klu.close >= high + size {
+                (high, high + size)
+            } else if klu.close <= low - size {
+                (low, low - size)
+            } else {
+                break;
+            };
+            (low, high) = (open.min(close), open.max(close));
+            let bar = PendingBar {
+                time: klu.time,
+                open,
+                high,
+                low,
+                close,
+                volume: trade_info.volume,
+                turnover: trade_info.turnover,
+            };
+            // 同一根KLU走出多块砖时，成交量只记在第一块上
+            trade_info = TradeInfo::default();
+            res.push(self.emit(bar)?);
+        }
+        self.brick = Some((low, high));
+        Ok(res)
+    }
+
+    fn push_range(&mut self, klu: &KLineUnit, size: f64) -> ChanResult<Vec<KLineUnit>> {
+        match &mut self.pending {
+            Some(bar) => bar.merge(klu),
+            None => self.pending = Some(PendingBar::new(klu)),
+        }
+        let bar = self.pending.as_ref().expect("pending bar is set above");
+        if bar.high - bar.low < size {
+            return Ok(Vec::new());
+        }
+        let bar = self.pending.take().expect("pending bar is set above");
+        Ok(vec![self.emit(bar)?])
+    }
+
+    /// 单根KLU的量不拆分，超过阈值的部分不结转到下一根
+    fn push_amount(&mut self, klu: &KLineUnit, size: f64) -> ChanResult<Vec<KLineUnit>> {
+        let volume = klu.trade_info.volume.unwrap_or(0.0);
+        self.amount += match self.mode {
+            BarMode::Dollar(_) => klu.trade_info.turnover.unwrap_or(klu.close * volume),
+            _ => volume,
+        };
+        match &mut self.pending {
+            Some(bar) => bar.merge(klu),
+            None => self.pending = Some(PendingBar::new(klu)),
+        }
+        if self.amount < size {
+            return Ok(Vec::new());
+        }
+        self.amount = 0.0;
+        let bar = self.pending.take().expect("pending bar is set above");
+        Ok(vec![self.emit(bar)?])
+    }
+
+    /// 同一时刻收出多根K线时顺延一秒，保证时间严格递增
+    fn emit(&mut self, bar: PendingBar) -> ChanResult<KLineUnit> {
+        let ts = match self.last_ts {
+            Some(last_ts) if bar.time.ts() <= last_ts => last_ts + 1,
+            _ => bar.time.ts(),
+        };
+        self.last_ts = Some(ts);
+        let time = if ts == bar.time.ts() {
+            bar.time
+        } else {
+            CTime::from_ts(ts)
+        };
+        let trade_info = TradeInfo {
+            volume: bar.volume,
+            turnover: bar.turnover,
+            ..TradeInfo::default()
+        };
+        let mut klu = KLineUnit::new(
+            time, bar.open, bar.high, bar.low, bar.close, trade_info, false,
+        )?;
+        klu.kl_type = self.kl_type;
+        Ok(klu)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn klu(idx: u32, high: f64, low: f64, close: f64) -> KLineUnit {
+        let t = CTime::new(2024, 1, 1, 9, 30 + idx, 0, false);
+        let trade_info = TradeInfo {
+            volume: Some(100.0),
+            ..TradeInfo::default()
+        };
+        KLineUnit::new(t, close, high, low, close, trade_info, false).unwrap()
+    }
+
+    #[test]
+    fn test_renko() {
+        let mut builder = BarBuilder::new(BarMode::Renko(BarSize::Fixed(1.0))).unwrap();
+        assert!(builder.push(&klu(0, 10.0, 10.0, 10.0)).unwrap().is_empty());
+        // 一根KLU走出两块上涨砖，第二块时间顺延一秒
+        let bricks = builder.push(&klu(1, 12.5, 10.0, 12.5)).unwrap();
+        assert_eq!(bricks.len(), 2);
+        assert_eq!((bricks[1].open, bricks[1].close), (11.0, 12.0));
+        assert_eq!(bricks[1].time.ts(), bricks[0].time.ts() + 1);
+        assert_eq!(bricks[0].trade_info.volume, Some(100.0));
+        assert_eq!(bricks[1].trade_info.volume, None);
+        // 反转要跌破上一块砖的开盘价再走一块
+        assert!(builder.push(&klu(2, 12.0, 10.5, 10.5)).unwrap().is_empty());
+        let bricks = builder.push(&klu(3, 10.5, 9.8, 9.8)).unwrap();
+        assert_eq!(bricks.len(), 1);
+        assert_eq!((bricks[0].open, bricks[0].close), (11.0, 10.0));
+        assert!(builder.flush().unwrap().is_none());
+
+        // 砖块相对跳空太小时报错，状态不变
+        let err = builder.push(&klu(4, 5000.0, 9.8, 5000.0)).unwrap_err();
+        assert_eq!(err.errcode, ErrCode::ParaError);
+        assert_eq!(builder.push(&klu(5, 10.0, 8.9, 8.9)).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_renko_flat_warm_up() {
+        let size = BarSize::Atr {
+            period: 2,
+            multiplier: 1.0,
+        };
+        let mut builder = BarBuilder::new(BarMode::Renko(size)).unwrap();
+        for i in 0..3 {
+            assert!(builder.push(&klu(i, 10.0, 10.0, 10.0)).unwrap().is_empty());
+        }
+        // 一字线的 ATR 为0，继续预热
+        assert_eq!(builder.size(), None);
+        assert!(builder.push(&klu(3, 11.0, 10.0, 11.0)).unwrap().is_empty());
+        assert_eq!(builder.size(), Some(0.5));
+        assert_eq!(builder.push(&klu(4, 12.0, 11.0, 12.0)).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_range_atr() {
+        let size = BarSize::Atr {
+            period: 2,
+            multiplier: 1.0,
+        };
+        let mut builder = BarBuilder::new(BarMode::Range(size)).unwrap();
+        assert!(builder.push(&klu(0, 11.0, 10.0, 10.5)).unwrap().is_empty());
+        assert!(builder.push(&klu(1, 12.0, 11.0, 11.5)).unwrap().is_empty());
+        assert_eq!(builder.size(), Some(1.25));
+
+        assert!(builder.push(&klu(2, 12.0, 11.5, 11.8)).unwrap().is_empty());
+        let bars = builder.push(&klu(3, 12.8, 11.9, 12.6)).unwrap();
+        assert_eq!(bars.len(), 1);
+        assert_eq!(
+            (bars[0].open, bars[0].high, bars[0].low),
+            (11.8, 12.8, 11.5)
+        );
+        assert_eq!(bars[0].trade_info.volume, Some(200.0));
+
+        builder.push(&klu(4, 12.7, 12.5, 12.6)).unwrap();
+        assert_eq!(builder.flush().unwrap().unwrap().close, 12.6);
+
+        let err = BarBuilder::new(BarMode::Renko(BarSize::Fixed(0.0))).unwrap_err();
+        assert_eq!(err.errcode, ErrCode::ParaError);
+        let err = BarBuilder::new(BarMode::Volume(-1.0)).unwrap_err();
+        assert_eq!(err.errcode, ErrCode::ParaError);
+    }
+
+    #[test]
+    fn test_volume_dollar() {
+        let mut builder = BarBuilder::new(BarMode::Volume(250.0)).unwrap();
+        assert!(builder.push(&klu(0, 11.0, 10.0, 10.5)).unwrap().is_empty());
+        assert!(builder.push(&klu(1, 12.0, 10.5, 11.5)).unwrap().is_empty());
+        let bars = builder.push(&klu(2, 11.8, 9.5, 10.0)).unwrap();
+        assert_eq!(bars.len(), 1);
+        assert_eq!(
+            (bars[0].open, bars[0].high, bars[0].low, bars[0].close),
+            (10.5, 12.0, 9.5, 10.0)
+        );
+        assert_eq!(bars[0].trade_info.volume, Some(300.0));
+        assert!(builder.push(&klu(3, 11.0, 10.0, 10.5)).unwrap().is_empty());
+
+        // 没有成交额时按 close * volume 估算
+        let mut builder = BarBuilder::new(BarMode::Dollar(2000.0)).unwrap();
+        assert!(builder.push(&klu(0, 11.0, 10.0, 10.0)).unwrap().is_empty());
+        assert_eq!(builder.push(&klu(1, 11.0, 10.0, 10.0)).unwrap().len(), 1);
+    }
+}
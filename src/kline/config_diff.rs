@@ -0,0 +1,173 @@
+use crate::chan_config::ChanConfig;
+use crate::common::chan_exception::ChanResult;
+use crate::common::ctime::CTime;
+use crate::common::enums::{FxType, KLineType};
+use crate::kline::fx_list::Fx;
+use crate::kline::kline_list::KLineList;
+use crate::kline::kline_unit::KLineUnit;
+
+/// 分形在比较时的标识：类型 + 顶点（底点）KLU的时间
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FxKey {
+    pub fx_type: FxType,
+    pub peak_time: CTime,
+}
+
+/// 同一份K线在两套配置下的分形差异
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfigDiff {
+    pub klc_cnt: (usize, usize),
+    pub fx_cnt: (usize, usize),
+    /// 两边都有的分形
+    pub same: Vec<FxKey>,
+    /// 同类型、中间KLC时间有重叠但顶点不同的分形对 (A, B)
+    pub shifted: Vec<(FxKey, FxKey)>,
+    pub only_in_a: Vec<FxKey>,
+    pub only_in_b: Vec<FxKey>,
+}
+
+impl ConfigDiff {
+    pub fn is_same(&self) -> bool {
+        self.shifted.is_empty() && self.only_in_a.is_empty() && self.only_in_b.is_empty()
+    }
+}
+
+/// 把 `klus` 分别按 `conf_a`、`conf_b` 计算一遍，比较两边的分形
+pub fn compare_configs(
+    klus: &[KLineUnit],
+    kl_type: Option<KLineType>,
+    conf_a: ChanConfig,
+    conf_b: ChanConfig,
+) -> ChanResult<ConfigDiff> {
+    let kl_list_a = run(klus, kl_type, conf_a)?;
+    let kl_list_b = run(klus, kl_type, conf_b)?;
+    let fx_a = fx_keys(&kl_list_a)?;
+    let fx_b = fx_keys(&kl_list_b)?;
+
+    let mut diff = ConfigDiff {
+        klc_cnt: (kl_list_a.len(), kl_list_b.len()),
+        fx_cnt: (fx_a.len(), fx_b.len()),
+        ..Default::default()
+    };
+    // 两边分形都按时间排列，顶点时间也严格递增，逐个归并即可
+    let mut rest_b: Vec<Option<&(FxKey, &Fx)>> = Vec::new();
+    let mut rest_a = Vec::new();
+    let mut iter_b = fx_b.iter().peekable();
+    for item_a in &fx_a {
+        while let Some(item_b) = iter_b.next_if(|b| b.0.peak_time < item_a.0.peak_time) {
+            rest_b.push(Some(item_b));
+        }
+        match iter_b.next_if(|b| b.0 == item_a.0) {
+            Some(_) => diff.same.push(item_a.0),
+            None => rest_a.push(item_a),
+        }
+    }
+    rest_b.extend(iter_b.map(Some));
+
+    // 各自的中间KLC互不重叠，能与某个A重叠的B只在 `begin` 之后的一小段里
+    let mut begin = 0;
+    for (key_a, fx_a) in rest_a {
+        while rest_b
+            .get(begin)
+            .is_some_and(|b| b.is_none_or(|(_, fx_b)| fx_b.time_end < fx_a.time_begin))
+        {
+            begin += 1;
+        }
+        let shifted = rest_b[begin..]
+            .iter_mut()
+            .take_while(|b| b.is_none_or(|(_, fx_b)| fx_b.time_begin <= fx_a.time_end))
+            .find(|b| {
+                b.is_some_and(|(key_b, fx_b)| {
+                    key_b.fx_type == key_a.fx_type && fx_a.time_begin <= fx_b.time_end
+                })
+            });
+        match shifted {
+            Some(b) => {
+                diff.shifted.push((*key_a, b.take().unwrap().0));
+            }
+            None => diff.only_in_a.push(*key_a),
+        }
+    }
+    diff.only_in_b = rest_b.into_iter().flatten().map(|b| b.0).collect();
+    Ok(diff)
+}
+
+pub(crate) fn run(
+    klus: &[KLineUnit],
+    kl_type: Option<KLineType>,
+    conf: ChanConfig,
+) -> ChanResult<KLineList> {
+    let mut kl_list = KLineList::with_config(kl_type, conf);
+    for klu in klus {
+        let mut klu = klu.clone();
+        klu.sub_kl_list.clear();
+        klu.sup_kl = None;
+        kl_list.add_single_klu(klu)?;
+    }
+    Ok(kl_list)
+}
+
+pub(crate) fn fx_keys(kl_list: &KLineList) -> ChanResult<Vec<(FxKey, &Fx)>> {
+    kl_list
+        .fx_list()
+        .iter()
+        .map(|fx| {
+            let key = FxKey {
+                fx_type: fx.fx_type,
+                peak_time: kl_list.fx_peak_klu(fx)?.time,
+            };
+            Ok((key, fx))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chan_config::FxConfig;
+    use crate::kline::trade_info::TradeInfo;
+
+    fn klus(bars: &[(f64, f64, f64, f64)]) -> Vec<KLineUnit> {
+        bars.iter()
+            .enumerate()
+            .map(|(i, &(open, high, low, close))| {
+                let t = CTime::new(2024, 1, 1 + i as u32, 0, 0, 0, false);
+                KLineUnit::new(t, open, high, low, close, TradeInfo::default(), false).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_compare_configs() {
+        let klus = klus(&[
+            (8.0, 10.0, 8.0, 10.0),
+            (9.0, 12.0, 9.0, 12.0),
+            (11.0, 11.0, 7.0, 7.0),
+            (8.0, 13.0, 8.0, 13.0),
+            (12.0, 12.0, 7.5, 8.0),
+        ]);
+        let diff = compare_configs(
+            &klus,
+            Some(KLineType::KDay),
+            ChanConfig::default(),
+            ChanConfig::default(),
+        )
+        .unwrap();
+        assert!(diff.is_same());
+        assert_eq!(diff.same.len(), 3);
+
+        let conf_b = ChanConfig {
+            fx_conf: FxConfig {
+                min_klc_gap: 2,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let diff =
+            compare_configs(&klus, Some(KLineType::KDay), ChanConfig::default(), conf_b).unwrap();
+        assert_eq!(diff.fx_cnt, (3, 2));
+        assert_eq!(diff.only_in_a.len(), 1);
+        assert_eq!(diff.only_in_a[0].fx_type, FxType::Bottom);
+        assert!(diff.shifted.is_empty() && diff.only_in_b.is_empty());
+    }
+}
@@ -0,0 +1,401 @@
+use std::ops::Index;
+
+use crate::common::ctime::CTime;
+use crate::common::enums::{FxType, KLineDir};
+use crate::common::symbol_info::SymbolInfo;
+use crate::kline::kline::KLine;
+
+/// 分形中间KLC与左右相邻KLC之间是否有缺口（缺口分型）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct FxGap {
+    pub with_pre: bool,
+    pub with_next: bool,
+}
+
+impl FxGap {
+    pub fn is_gap_fx(&self) -> bool {
+        self.with_pre || self.with_next
+    }
+}
+
+/// 导出时的一个字段，JSON 中是否加引号由字段本身的类型决定
+enum Cell {
+    Str(String),
+    Int(u64),
+    Num(f64),
+    Bool(bool),
+}
+
+impl Cell {
+    fn to_csv(&self) -> String {
+        match self {
+            Cell::Str(v) => v.clone(),
+            Cell::Int(v) => v.to_string(),
+            Cell::Num(v) => v.to_string(),
+            Cell::Bool(v) => v.to_string(),
+        }
+    }
+
+    /// JSON 不能表示 NaN 和无穷大，写成 null
+    fn to_json(&self) -> String {
+        match self {
+            Cell::Str(v) => format!("\"{v}\""),
+            Cell::Num(v) if !v.is_finite() => "null".to_string(),
+            _ => self.to_csv(),
+        }
+    }
+}
+
+/// 一个顶/底分形，位置由分形中间那根KLC决定
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fx {
+    /// 递增分配的编号，`trim_before` 和快照恢复后都不变，可用于外部长期引用；
+    /// 回补合并和回退重算的分形会分配新编号，这样的列表和丢弃过前部数据的列表都不能再用 `reconstruct_at` 重建
+    pub id: u64,
+    pub idx: usize,
+    pub fx_type: FxType,
+    pub klc_idx: usize,
+    pub time_begin: CTime,
+    pub time_end: CTime,
+    pub high: f64,
+    pub low: f64,
+    /// 右侧KLC走完之前分形还可能被改写，此时为 false
+    pub is_sure: bool,
+    /// 以仍在走的最后一根KLC为中间KLC推测出来的分形，随时可能消失
+    pub is_virtual: bool,
+    pub gap: FxGap,
+}
+
+impl Fx {
+    fn new(id: u64, idx: usize, klc: &KLine, gap: FxGap) -> Self {
+        Self::with_type(id, idx, klc, klc.fx(), gap)
+    }
+
+    /// 最后一根KLC上的虚分形：向上的KLC只要后面回落就成顶，向下的反之，与虚笔的判断方式一致
+    ///
+    /// `id` 取该分形确认后将会分配到的编号
+    pub(crate) fn new_virtual(id: u64, idx: usize, klc: &KLine, gap: FxGap) -> Option<Self> {
+        let fx_type = match klc.dir() {
+            KLineDir::Up => FxType::Top,
+            KLineDir::Down => FxType::Bottom,
+            _ => return None,
+        };
+        Some(Self {
+            is_virtual: true,
+            ..Self::with_type(id, idx, klc, fx_type, gap)
+        })
+    }
+
+    fn with_type(id: u64, idx: usize, klc: &KLine, fx_type: FxType, gap: FxGap) -> Self {
+        Self {
+            id,
+            idx,
+            fx_type,
+            klc_idx: klc.idx,
+            time_begin: klc.time_begin(),
+            time_end: klc.time_end(),
+            high: klc.high(),
+            low: klc.low(),
+            is_sure: false,
+            is_virtual: false,
+            gap,
+        }
+    }
+
+    /// 导出为一行字段，顺序与 `FxList::CSV_HEADER` 一致，给出 `symbol` 时高低点按最小变动价位取整
+    fn to_row(&self, symbol: Option<&SymbolInfo>) -> [Cell; 12] {
+        let price = |p: f64| symbol.map_or(p, |s| s.round_price(p));
+        let fx_type = match self.fx_type {
+            FxType::Top => "top",
+            FxType::Bottom => "bottom",
+            FxType::Unknown => "unknown",
+        };
+        [
+            Cell::Int(self.id),
+            Cell::Int(self.idx as u64),
+            Cell::Str(fx_type.to_string()),
+            Cell::Int(self.klc_idx as u64),
+            Cell::Str(self.time_begin.to_str()),
+            Cell::Str(self.time_end.to_str()),
+            Cell::Num(price(self.high)),
+            Cell::Num(price(self.low)),
+            Cell::Bool(self.is_sure),
+            Cell::Bool(self.is_virtual),
+            Cell::Bool(self.gap.with_pre),
+            Cell::Bool(self.gap.with_next),
+        ]
+    }
+}
+
+/// 随 `KLineList` 一起维护的所有分形，按KLC顺序排列
+#[derive(Debug, Clone, Default)]
+pub struct FxList {
+    lst: Vec<Fx>,
+    next_id: u64,
+}
+
+impl FxList {
+    pub const CSV_HEADER: [&'static str; 12] = [
+        "id",
+        "idx",
+        "fx_type",
+        "klc_idx",
+        "time_begin",
+        "time_end",
+        "high",
+        "low",
+        "is_sure",
+        "is_virtual",
+        "gap_with_pre",
+        "gap_with_next",
+    ];
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 恢复快照时使用，`next_id` 原样保留才能让之后的分形编号接得上
+    pub(crate) fn from_parts(lst: Vec<Fx>, next_id: u64) -> Self {
+        Self { lst, next_id }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lst.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lst.is_empty()
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&Fx> {
+        self.lst.get(idx)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Fx> {
+        self.lst.iter()
+    }
+
+    pub fn pre(&self, fx: &Fx) -> Option<&Fx> {
+        fx.idx.checked_sub(1).and_then(|idx| self.lst.get(idx))
+    }
+
+    pub fn next(&self, fx: &Fx) -> Option<&Fx> {
+        self.lst.get(fx.idx + 1)
+    }
+
+    /// 中间KLC为 `klc_idx` 的分形
+    pub fn get_by_klc(&self, klc_idx: usize) -> Option<&Fx> {
+        let pos = self.lst.partition_point(|fx| fx.klc_idx < klc_idx);
+        self.lst.get(pos).filter(|fx| fx.klc_idx == klc_idx)
+    }
+
+    /// 下一个新分形将分配到的编号
+    pub fn next_id(&self) -> u64 {
+        self.next_id
+    }
+
+    /// 按编号查找，编号随列表顺序递增，二分定位；已被删除的分形返回 None
+    pub fn get_by_id(&self, id: u64) -> Option<&Fx> {
+        let pos = self.lst.partition_point(|fx| fx.id < id);
+        self.lst.get(pos).filter(|fx| fx.id == id)
+    }
+
+    /// 与 [begin_ts, end_ts] 有重叠的分形
+    pub fn query_range(&self, begin_ts: i64, end_ts: i64) -> &[Fx] {
+        let begin = self.lst.partition_point(|fx| fx.time_end.ts() < begin_ts);
+        let end = self.lst.partition_point(|fx| fx.time_begin.ts() <= end_ts);
+        &self.lst[begin..end.max(begin)]
+    }
+
+    /// 导出为CSV文本（带表头），用于统计分形质量
+    pub fn to_csv(&self) -> String {
+        self.to_csv_with(None)
+    }
+
+    /// 同 `to_csv`，给出 `symbol` 时高低点按最小变动价位取整
+    pub fn to_csv_with(&self, symbol: Option<&SymbolInfo>) -> String {
+        let mut out = Self::CSV_HEADER.join(",");
+        for fx in &self.lst {
+            out.push('\n');
+            let row: Vec<String> = fx.to_row(symbol).iter().map(Cell::to_csv).collect();
+            out.push_str(&row.join(","));
+        }
+        out
+    }
+
+    /// 导出为JSON数组，每个分形一个对象，字段同 `CSV_HEADER`
+    pub fn to_json(&self) -> String {
+        self.to_json_with(None)
+    }
+
+    /// 同 `to_json`，给出 `symbol` 时高低点按最小变动价位取整
+    pub fn to_json_with(&self, symbol: Option<&SymbolInfo>) -> String {
+        let items: Vec<String> = self
+            .lst
+            .iter()
+            .map(|fx| {
+                let fields: Vec<String> = Self::CSV_HEADER
+                    .iter()
+                    .zip(fx.to_row(symbol))
+                    .map(|(key, cell)| format!("\"{key}\":{}", cell.to_json()))
+                    .collect();
+                format!("{{{}}}", fields.join(","))
+            })
+            .collect();
+        format!("[{}]", items.join(","))
+    }
+
+    /// 新KLC开出后调用：`klc` 为刚算完分形的倒数第二根，它之前那根的分形就此确定
+    pub(crate) fn update(&mut self, klc: &KLine, gap: FxGap) {
+        if let Some(last) = self.lst.last_mut() {
+            if last.klc_idx + 1 == klc.idx {
+                last.is_sure = true;
+            }
+        }
+        if klc.fx() != FxType::Unknown {
+            self.push(klc, gap, None);
+        }
+    }
+
+    /// `reuse_id` 为 None 时分配新编号
+    fn push(&mut self, klc: &KLine, gap: FxGap, reuse_id: Option<u64>) {
+        let id = reuse_id.unwrap_or_else(|| {
+            self.next_id += 1;
+            self.next_id - 1
+        });
+        let idx = self.lst.len();
+        self.lst.push(Fx::new(id, idx, klc, gap));
+    }
+
+    /// `klc` 的分形被重算后同步记录，`klc` 必须是最后一个可能有分形的KLC
+    ///
+    /// 重算前后同一KLC上都有同类型分形时沿用原编号
+    pub(crate) fn refresh(&mut self, klc: &KLine, gap: FxGap) {
+        let mut reuse_id = None;
+        if self.lst.last().is_some_and(|fx| fx.klc_idx == klc.idx) {
+            reuse_id = self
+                .lst
+                .pop()
+                .filter(|fx| fx.fx_type == klc.fx())
+                .map(|fx| fx.id);
+        }
+        if klc.fx() != FxType::Unknown {
+            self.push(klc, gap, reuse_id);
+        }
+    }
+
+    /// KLC被回退到只剩 `klc_cnt` 根时调用：最后一根KLC没有右侧，其上的分形作废，倒数第二根的分形不再确定
+    pub(crate) fn truncate_to_klc_cnt(&mut self, klc_cnt: usize) {
+        let pos = self.lst.partition_point(|fx| fx.klc_idx + 1 < klc_cnt);
+        self.lst.truncate(pos);
+        if let Some(last) = self.lst.last_mut() {
+            if last.klc_idx + 2 == klc_cnt {
+                last.is_sure = false;
+            }
+        }
+    }
+
+    /// 配合 `KLineList::trim_before` 删除前 `klc_cnt` 根KLC上的分形
+    pub(crate) fn trim_before_klc(&mut self, klc_cnt: usize) {
+        let pos = self.lst.partition_point(|fx| fx.klc_idx < klc_cnt);
+        self.lst.drain(..pos);
+        self.shift_idx(klc_cnt);
+    }
+
+    /// 第 `klc_begin` 根KLC起的分形副本，下标同 `trim_before_klc` 一样重排，编号不变
+    pub(crate) fn tail_from_klc(&self, klc_begin: usize) -> Self {
+        let pos = self.lst.partition_point(|fx| fx.klc_idx < klc_begin);
+        let mut tail = Self::from_parts(self.lst[pos..].to_vec(), self.next_id);
+        tail.shift_idx(klc_begin);
+        tail
+    }
+
+    fn shift_idx(&mut self, klc_offset: usize) {
+        for (idx, fx) in self.lst.iter_mut().enumerate() {
+            fx.idx = idx;
+            fx.klc_idx -= klc_offset;
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.lst.clear();
+    }
+}
+
+impl Index<usize> for FxList {
+    type Output = Fx;
+
+    fn index(&self, idx: usize) -> &Fx {
+        &self.lst[idx]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kline::kline_unit::KLineUnit;
+    use crate::kline::trade_info::TradeInfo;
+
+    fn klc(idx: usize, fx: FxType) -> KLine {
+        let t = CTime::new(2024, 1, 1 + idx as u32, 0, 0, 0, false);
+        let klu = KLineUnit::from_ohlcv(t, 9.0, 10.0, 8.0, 9.0, TradeInfo::default()).unwrap();
+        let mut klc = KLine::new(&klu, idx, KLineDir::Up);
+        klc.set_fx(fx);
+        klc
+    }
+
+    #[test]
+    fn test_refresh() {
+        let mut fx_list = FxList::new();
+        fx_list.update(&klc(1, FxType::Top), FxGap::default());
+        fx_list.update(&klc(2, FxType::Unknown), FxGap::default());
+        assert!(fx_list[0].is_sure);
+        fx_list.update(&klc(3, FxType::Bottom), FxGap::default());
+
+        // 重算后分形消失
+        fx_list.refresh(&klc(3, FxType::Unknown), FxGap::default());
+        assert_eq!(fx_list.len(), 1);
+        // 重算后出现新分形
+        fx_list.refresh(&klc(3, FxType::Top), FxGap::default());
+        assert_eq!((fx_list.len(), fx_list[1].fx_type), (2, FxType::Top));
+        assert!(!fx_list[1].is_sure);
+        // 编号不回收，同类型重算沿用原编号
+        assert_eq!(fx_list[1].id, 2);
+        fx_list.refresh(&klc(3, FxType::Top), FxGap::default());
+        assert_eq!(fx_list[1].id, 2);
+        assert_eq!(fx_list.get_by_id(2), Some(&fx_list[1]));
+        assert!(fx_list.get_by_id(1).is_none());
+    }
+
+    #[test]
+    fn test_export() {
+        let mut fx_list = FxList::new();
+        fx_list.update(&klc(1, FxType::Top), FxGap::default());
+        let csv = fx_list.to_csv();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next().unwrap(), FxList::CSV_HEADER.join(","));
+        assert_eq!(
+            lines.next().unwrap(),
+            "0,0,top,1,2024/01/02,2024/01/02,10,8,false,false,false,false"
+        );
+        assert_eq!(
+            fx_list.to_json(),
+            "[{\"id\":0,\"idx\":0,\"fx_type\":\"top\",\"klc_idx\":1,\"time_begin\":\"2024/01/02\",\
+             \"time_end\":\"2024/01/02\",\"high\":10,\"low\":8,\"is_sure\":false,\
+             \"is_virtual\":false,\"gap_with_pre\":false,\"gap_with_next\":false}]"
+        );
+        assert_eq!(FxList::new().to_json(), "[]");
+
+        fx_list.lst[0].high = 10.07;
+        let symbol = SymbolInfo::new("X", 0.05, 1, 2, "USD").unwrap();
+        assert!(fx_list.to_csv_with(Some(&symbol)).contains(",10.05,8,"));
+        assert!(fx_list
+            .to_json_with(Some(&symbol))
+            .contains("\"high\":10.05,"));
+
+        fx_list.lst[0].high = f64::INFINITY;
+        fx_list.lst[0].low = f64::NAN;
+        let json = fx_list.to_json();
+        assert!(json.contains("\"high\":null,\"low\":null"));
+    }
+}
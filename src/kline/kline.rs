@@ -0,0 +1,307 @@
+use std::fmt;
+use std::ops::{Deref, DerefMut, RangeInclusive};
+
+use crate::chan_config::{CombineDirPolicy, EqualExtremePolicy};
+use crate::combiner::kline_combiner::Combiner;
+use crate::common::chan_element::ChanElement;
+use crate::common::chan_exception::ChanResult;
+use crate::common::enums::{FxType, KLineDir, KLineType};
+use crate::kline::kline_unit::KLineUnit;
+
+/// 合并后的K线，KLU本身由 `KLineList` 持有，合并器里只记录KLU下标
+#[derive(Debug, Clone)]
+pub struct KLine {
+    pub idx: usize,
+    pub kl_type: Option<KLineType>,
+    combiner: Combiner<KLineUnit>,
+}
+
+impl KLine {
+    pub fn new(klu: &KLineUnit, idx: usize, dir: KLineDir) -> Self {
+        Self {
+            idx,
+            kl_type: klu.kl_type,
+            combiner: Combiner::new(klu, dir),
+        }
+    }
+
+    pub(crate) fn from_parts(
+        idx: usize,
+        kl_type: Option<KLineType>,
+        combiner: Combiner<KLineUnit>,
+    ) -> Self {
+        Self {
+            idx,
+            kl_type,
+            combiner,
+        }
+    }
+
+    /// 本KLC包含的KLU下标（闭区间），KLC内的KLU总是连续的
+    pub fn klu_range(&self) -> RangeInclusive<usize> {
+        let lst = self.combiner.lst();
+        lst[0]..=lst[lst.len() - 1]
+    }
+
+    /// 按 `policy` 决定合并方向、按 `equal` 处理高低点相等后尝试把 `klu` 合并进来，
+    /// 返回值同 `Combiner::try_add`
+    pub fn try_add_klu(
+        &mut self,
+        klu: &KLineUnit,
+        policy: CombineDirPolicy,
+        equal: EqualExtremePolicy,
+    ) -> ChanResult<KLineDir> {
+        if equal == EqualExtremePolicy::Break {
+            let (high_eq, low_eq) = (klu.high == self.high(), klu.low == self.low());
+            if high_eq && !low_eq {
+                return Ok(if klu.low < self.low() {
+                    KLineDir::Down
+                } else {
+                    KLineDir::Up
+                });
+            }
+            if low_eq && !high_eq {
+                return Ok(if klu.high > self.high() {
+                    KLineDir::Up
+                } else {
+                    KLineDir::Down
+                });
+            }
+        }
+        let merge_dir = match policy {
+            CombineDirPolicy::Close if klu.close > klu.open => KLineDir::Up,
+            CombineDirPolicy::Close if klu.close < klu.open => KLineDir::Down,
+            _ => self.dir(),
+        };
+        self.combiner.try_add_with_dir(klu, false, None, merge_dir)
+    }
+
+    /// 删除前面的KLC/KLU之后整体平移下标
+    pub(crate) fn shift_idx(&mut self, klc_offset: usize, klu_offset: usize) {
+        self.idx -= klc_offset;
+        self.combiner.shift_item_idx(klu_offset);
+    }
+}
+
+impl Deref for KLine {
+    type Target = Combiner<KLineUnit>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.combiner
+    }
+}
+
+impl DerefMut for KLine {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.combiner
+    }
+}
+
+impl ChanElement for KLine {
+    fn idx(&self) -> usize {
+        self.idx
+    }
+
+    fn begin_ts(&self) -> i64 {
+        self.time_begin().ts()
+    }
+
+    fn end_ts(&self) -> i64 {
+        self.time_end().ts()
+    }
+
+    fn high(&self) -> f64 {
+        self.combiner.high()
+    }
+
+    fn low(&self) -> f64 {
+        self.combiner.low()
+    }
+
+    fn dir(&self) -> KLineDir {
+        self.combiner.dir()
+    }
+
+    /// KLC的边界在后一根KLC开出时才确定，KLC本身不知道自己是不是最后一根，按已确定处理
+    fn is_sure(&self) -> bool {
+        true
+    }
+}
+
+impl fmt::Display for KLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fx_token = match self.fx() {
+            FxType::Top => "^",
+            FxType::Bottom => "_",
+            FxType::Unknown => "",
+        };
+        write!(
+            f,
+            "{}th{}:{}~{}({:?}|{}) low={} high={}",
+            self.idx,
+            fx_token,
+            self.time_begin(),
+            self.time_end(),
+            self.kl_type,
+            self.len(),
+            self.low(),
+            self.high()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::ctime::CTime;
+    use crate::kline::trade_info::TradeInfo;
+
+    fn klu(idx: usize, high: f64, low: f64) -> KLineUnit {
+        let t = CTime::new(2024, 1, 1 + idx as u32, 0, 0, 0, false);
+        let mut klu = KLineUnit::new(t, low, high, low, high, TradeInfo::default(), false).unwrap();
+        klu.idx = idx;
+        klu
+    }
+
+    #[test]
+    fn test_try_add_dir() {
+        let mut klc = KLine::new(&klu(0, 10.0, 8.0), 0, KLineDir::Up);
+        assert_eq!(
+            klc.try_add(&klu(1, 11.0, 9.0), false, None).unwrap(),
+            KLineDir::Up
+        );
+        assert_eq!(
+            klc.try_add(&klu(1, 9.0, 7.0), false, None).unwrap(),
+            KLineDir::Down
+        );
+        assert_eq!(klc.len(), 1);
+    }
+
+    #[test]
+    fn test_try_add_combine_by_dir() {
+        // 向上合并取高高
+        let mut klc = KLine::new(&klu(0, 10.0, 8.0), 0, KLineDir::Up);
+        assert_eq!(
+            klc.try_add(&klu(1, 9.5, 8.5), false, None).unwrap(),
+            KLineDir::Combine
+        );
+        assert_eq!((klc.high(), klc.low()), (10.0, 8.5));
+        assert_eq!(
+            klc.try_add(&klu(2, 11.0, 7.0), false, None).unwrap(),
+            KLineDir::Combine
+        );
+        assert_eq!((klc.high(), klc.low()), (11.0, 8.5));
+        assert_eq!(klc.len(), 3);
+        assert_eq!(klc.time_end(), klu(2, 11.0, 7.0).time);
+
+        // 向下合并取低低
+        let mut klc = KLine::new(&klu(0, 10.0, 8.0), 0, KLineDir::Down);
+        klc.try_add(&klu(1, 9.5, 8.5), false, None).unwrap();
+        assert_eq!((klc.high(), klc.low()), (9.5, 8.0));
+    }
+
+    #[test]
+    fn test_try_add_included() {
+        let mut klc = KLine::new(&klu(0, 10.0, 8.0), 0, KLineDir::Up);
+        assert_eq!(
+            klc.try_add(&klu(1, 11.0, 7.0), true, None).unwrap(),
+            KLineDir::Included
+        );
+        assert_eq!(klc.len(), 1);
+        // 顶部相等不合并
+        assert_eq!(
+            klc.try_add(&klu(1, 10.0, 7.0), true, Some(1)).unwrap(),
+            KLineDir::Down
+        );
+    }
+
+    fn klc(idx: usize, high: f64, low: f64) -> KLine {
+        KLine::new(&klu(idx, high, low), idx, KLineDir::Up)
+    }
+
+    #[test]
+    fn test_update_fx() {
+        let mut top = klc(1, 12.0, 10.0);
+        top.update_fx(&klc(0, 11.0, 9.0), &klc(2, 11.0, 8.0), false, None);
+        assert_eq!(top.fx(), FxType::Top);
+
+        let mut bottom = klc(1, 9.0, 7.0);
+        bottom.update_fx(&klc(0, 11.0, 9.0), &klc(2, 10.0, 8.0), false, None);
+        assert_eq!(bottom.fx(), FxType::Bottom);
+
+        let mut none = klc(1, 12.0, 10.0);
+        none.update_fx(&klc(0, 11.0, 9.0), &klc(2, 13.0, 11.0), false, None);
+        assert_eq!(none.fx(), FxType::Unknown);
+    }
+
+    #[test]
+    fn test_update_fx_equal_high() {
+        // 普通模式下相邻KLC不可能高点相等，exclude_included 模式下由 allow_top_equal 决定
+        let mut top = klc(1, 12.0, 10.0);
+        top.update_fx(&klc(0, 11.0, 9.0), &klc(2, 12.0, 8.0), true, None);
+        assert_eq!(top.fx(), FxType::Unknown);
+        top.update_fx(&klc(0, 11.0, 9.0), &klc(2, 12.0, 8.0), true, Some(1));
+        assert_eq!(top.fx(), FxType::Top);
+
+        let mut bottom = klc(1, 9.0, 7.0);
+        bottom.update_fx(&klc(0, 11.0, 9.0), &klc(2, 10.0, 7.0), true, None);
+        assert_eq!(bottom.fx(), FxType::Unknown);
+        bottom.update_fx(&klc(0, 11.0, 9.0), &klc(2, 10.0, 7.0), true, Some(-1));
+        assert_eq!(bottom.fx(), FxType::Bottom);
+    }
+
+    #[test]
+    fn test_flat_klu_keeps_peak() {
+        // 一字K线与高点重合时不改变高低点
+        let mut klc = KLine::new(&klu(0, 10.0, 8.0), 0, KLineDir::Up);
+        klc.try_add(&klu(1, 10.0, 10.0), false, None).unwrap();
+        assert_eq!((klc.high(), klc.low()), (10.0, 8.0));
+    }
+
+    #[test]
+    fn test_try_add_klu_close_policy() {
+        // 向上的KLC里合并一根收阴的KLU，按收盘方向取低低
+        let t = CTime::new(2024, 1, 2, 0, 0, 0, false);
+        let mut bear = KLineUnit::new(t, 9.4, 9.5, 8.5, 8.6, TradeInfo::default(), false).unwrap();
+        bear.idx = 1;
+        let mut klc = KLine::new(&klu(0, 10.0, 8.0), 0, KLineDir::Up);
+        klc.try_add_klu(&bear, CombineDirPolicy::Close, EqualExtremePolicy::Merge)
+            .unwrap();
+        assert_eq!((klc.high(), klc.low()), (9.5, 8.0));
+        assert_eq!(klc.dir(), KLineDir::Up);
+
+        let mut klc = KLine::new(&klu(0, 10.0, 8.0), 0, KLineDir::Up);
+        klc.try_add_klu(&bear, CombineDirPolicy::Trend, EqualExtremePolicy::Merge)
+            .unwrap();
+        assert_eq!((klc.high(), klc.low()), (10.0, 8.5));
+    }
+
+    #[test]
+    fn test_try_add_klu_equal_extreme() {
+        let policy = CombineDirPolicy::Trend;
+        let mut klc = KLine::new(&klu(0, 10.0, 8.0), 0, KLineDir::Up);
+        assert_eq!(
+            klc.try_add_klu(&klu(1, 10.0, 7.0), policy, EqualExtremePolicy::Break)
+                .unwrap(),
+            KLineDir::Down
+        );
+        assert_eq!(
+            klc.try_add_klu(&klu(1, 9.0, 8.0), policy, EqualExtremePolicy::Break)
+                .unwrap(),
+            KLineDir::Down
+        );
+        assert_eq!(klc.len(), 1);
+        // 高低点都相等仍然合并
+        assert_eq!(
+            klc.try_add_klu(&klu(1, 10.0, 8.0), policy, EqualExtremePolicy::Break)
+                .unwrap(),
+            KLineDir::Combine
+        );
+        assert_eq!(
+            klc.try_add_klu(&klu(2, 10.0, 7.0), policy, EqualExtremePolicy::Merge)
+                .unwrap(),
+            KLineDir::Combine
+        );
+        assert_eq!((klc.high(), klc.low()), (10.0, 8.0));
+    }
+}
@@ -0,0 +1,2194 @@
+use std::fmt::{self, Write};
+use std::ops::Index;
+use std::time::Instant;
+
+use crate::bi::bi_config::BiConfig;
+use crate::chan_config::{ChanConfig, FxConfig, InvalidBarPolicy};
+use crate::common::chan_element;
+use crate::common::chan_exception::{ChanException, ChanResult, ErrCode};
+use crate::common::ctime::CTime;
+use crate::common::enums::{FxType, KLineDir, KLineType};
+use crate::common::func_util::{has_overlap, kltype_lt_day};
+use crate::common::messages::{tr, MsgKey};
+use crate::common::profiler::{Phase, Profiler};
+use crate::common::symbol_info::SymbolInfo;
+use crate::kline::alert::{AlertEngine, AlertEvent, AlertRule};
+use crate::kline::fx_list::{Fx, FxGap, FxList};
+use crate::kline::kline::KLine;
+use crate::kline::kline_unit::{KLineUnit, KluInput};
+use crate::kline::snapshot::{self, ChanSnapshot, ListState};
+use crate::math::macd::MacdCalculator;
+
+/// `KLineList::element_at` 的结果，某一时刻所处的各级元素
+#[derive(Debug, Clone, Copy)]
+pub struct ElementsAt<'a> {
+    pub klu: &'a KLineUnit,
+    pub klc: &'a KLine,
+    /// 该KLC是分形中间KLC时才有
+    pub fx: Option<&'a Fx>,
+}
+
+/// `KLineList::summary` 的统计结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct KLineSummary {
+    pub klu_cnt: usize,
+    pub klc_cnt: usize,
+    pub top_fx_cnt: usize,
+    pub bottom_fx_cnt: usize,
+    /// 右侧KLC还没走完、仍可能被改写的分形数
+    pub unsure_fx_cnt: usize,
+    pub invalid_bar_cnt: usize,
+    pub halted_klu_cnt: usize,
+    /// 平均每根KLC包含的KLU数
+    pub avg_klc_len: f64,
+    /// 第一根和最后一根KLU的时间
+    pub time_range: Option<(CTime, CTime)>,
+}
+
+/// `KLineList::check_invariants` 发现的不一致
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvariantViolation {
+    /// 第 `klu_idx` 根KLU记录的下标是 `found`
+    KluIdx { klu_idx: usize, found: usize },
+    /// 第 `klu_idx` 根KLU的时间没有晚于上一根（逐笔数据允许相等）
+    KluTimeOrder { klu_idx: usize },
+    /// KLU记录的所属KLC与实际包含它的KLC不符
+    KluKlc {
+        klu_idx: usize,
+        klc_idx: usize,
+        found: Option<usize>,
+    },
+    /// 第 `klc_idx` 根KLC记录的下标是 `found`
+    KlcIdx { klc_idx: usize, found: usize },
+    /// KLC包含的KLU没有紧接上一根KLC，或者越界
+    KlcNotContiguous { klc_idx: usize },
+    /// 所有KLC只覆盖了前 `covered` 根KLU
+    KluNotCovered { klu_cnt: usize, covered: usize },
+    /// 第 `fx_idx` 个分形记录的下标是 `found`
+    FxIdx { fx_idx: usize, found: usize },
+    /// 分形指向的KLC不存在或KLC上的分形类型不符
+    FxKlc { fx_id: u64, klc_idx: usize },
+    /// 分形的KLC下标或编号没有严格递增
+    FxOrder { fx_id: u64 },
+    /// KLC上有分形但 `fx_list` 里没有记录
+    FxMissing { klc_idx: usize },
+    /// 分形是否确定与右侧KLC是否走完不符
+    FxSure { fx_id: u64, is_sure: bool },
+}
+
+#[derive(Debug, Clone)]
+pub struct KLineList {
+    pub kl_type: Option<KLineType>,
+    pub config: ChanConfig,
+    lst: Vec<KLine>,      // 合并后的K线列表
+    klus: Vec<KLineUnit>, // 本级别每一根单位K线
+    fx_list: FxList,
+    invalid_bar_cnt: usize,
+    trimmed_klu_cnt: usize, // trim_before 累计丢弃的KLU数
+    rebuilt: bool,          // 回补合并或回退重算过，分形编号与直接重放不再一致
+    profiler: Option<Profiler>,
+    alerts: AlertEngine,
+    macd: Option<MacdCalculator>, // 配置里任一周期为0时不计算
+    symbol: Option<SymbolInfo>,
+}
+
+impl KLineList {
+    pub fn new(kl_type: Option<KLineType>) -> Self {
+        Self::with_config(kl_type, ChanConfig::default())
+    }
+
+    pub fn with_config(kl_type: Option<KLineType>, config: ChanConfig) -> Self {
+        let macd = MacdCalculator::new(config.macd.fast, config.macd.slow, config.macd.signal);
+        Self {
+            kl_type,
+            config,
+            lst: Vec::new(),
+            klus: Vec::new(),
+            fx_list: FxList::new(),
+            invalid_bar_cnt: 0,
+            trimmed_klu_cnt: 0,
+            rebuilt: false,
+            profiler: None,
+            alerts: AlertEngine::new(),
+            macd: macd.ok(),
+            symbol: None,
+        }
+    }
+
+    /// 设置标的的交易属性：之后按 `on_invalid_bar` 校验价格是否落在最小变动价位上，
+    /// 导出分形时按它取整
+    pub fn set_symbol_info(&mut self, info: SymbolInfo) {
+        self.symbol = Some(info);
+    }
+
+    pub fn symbol_info(&self) -> Option<&SymbolInfo> {
+        self.symbol.as_ref()
+    }
+
+    /// 开始按阶段记录每根KLU的计算耗时
+    pub fn enable_profiling(&mut self) {
+        self.profiler.get_or_insert_with(Profiler::new);
+    }
+
+    /// 未开启记录时为 None
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    pub fn fx_list(&self) -> &FxList {
+        &self.fx_list
+    }
+
+    /// 以正在走的最后一根KLC为中间KLC的虚分形，不记录进 `fx_list`，每次按最新状态重新计算
+    ///
+    /// 第一根KLC没有方向可言，KLC不足两根时返回 None
+    pub fn virtual_fx(&self) -> Option<Fx> {
+        if self.lst.len() < 2 {
+            return None;
+        }
+        let last_idx = self.lst.len() - 1;
+        Fx::new_virtual(
+            self.fx_list.next_id(),
+            self.fx_list.len(),
+            &self.lst[last_idx],
+            self.fx_gap(last_idx),
+        )
+    }
+
+    /// 按 `on_invalid_bar` 被丢弃或修正过的KLU数量
+    pub fn invalid_bar_cnt(&self) -> usize {
+        self.invalid_bar_cnt
+    }
+
+    /// `trim_before` 累计丢弃的KLU数量，不为0时前部历史已经无法重放
+    pub fn trimmed_klu_cnt(&self) -> usize {
+        self.trimmed_klu_cnt
+    }
+
+    pub fn len(&self) -> usize {
+        self.lst.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lst.is_empty()
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&KLine> {
+        self.lst.get(idx)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, KLine> {
+        self.lst.iter()
+    }
+
+    pub fn klu_cnt(&self) -> usize {
+        self.klus.len()
+    }
+
+    pub fn klu(&self, idx: usize) -> Option<&KLineUnit> {
+        self.klus.get(idx)
+    }
+
+    /// 某根KLC包含的所有KLU
+    pub fn klc_units(&self, klc: &KLine) -> &[KLineUnit] {
+        &self.klus[klc.klu_range()]
+    }
+
+    pub fn add_single_klu(&mut self, mut klu: KLineUnit) -> ChanResult<()> {
+        if let Some(policy) = self.config.on_invalid_bar {
+            if !self.apply_invalid_bar_policy(&mut klu, policy)? {
+                return Ok(());
+            }
+        }
+        let pre_close = self.klus.last().map(|klu| klu.close);
+        klu.is_halted = pre_close.is_some_and(|pre_close| is_halt_bar(&klu, pre_close));
+        self.add_klu_unchecked(klu)?;
+        if !self.alerts.is_empty() {
+            let klu = self.klus.last().expect("klu just added");
+            self.alerts.evaluate(pre_close, klu, &self.fx_list);
+        }
+        Ok(())
+    }
+
+    /// 注册价格提醒规则，返回规则编号；之后每根KLU进来时检查
+    pub fn add_alert(&mut self, rule: AlertRule) -> usize {
+        self.alerts.add_rule(rule)
+    }
+
+    pub fn remove_alert(&mut self, rule_id: usize) -> Option<AlertRule> {
+        self.alerts.remove_rule(rule_id)
+    }
+
+    /// 取走所有已触发的提醒
+    pub fn drain_alerts(&mut self) -> Vec<AlertEvent> {
+        self.alerts.drain_events()
+    }
+
+    /// 校验不通过时按策略处理，返回是否接收该KLU
+    ///
+    /// `Clamp` 每修正一处就重新校验，直到通过或遇到修正不了的问题（时间倒退、同一问题修正后仍在）时丢弃
+    fn apply_invalid_bar_policy(
+        &mut self,
+        klu: &mut KLineUnit,
+        policy: InvalidBarPolicy,
+    ) -> ChanResult<bool> {
+        let Some(mut err) = self.check_klu(klu) else {
+            return Ok(true);
+        };
+        if policy == InvalidBarPolicy::Error {
+            return Err(err);
+        }
+        self.invalid_bar_cnt += 1;
+        if policy == InvalidBarPolicy::Skip {
+            return Ok(false);
+        }
+        let mut fixed = Vec::new();
+        loop {
+            match err.errcode {
+                ErrCode::PriceBelowZero | ErrCode::KlDataInvalid | ErrCode::PriceNotOnTick
+                    if !fixed.contains(&err.errcode) =>
+                {
+                    self.clamp_klu(klu, err.errcode);
+                    fixed.push(err.errcode);
+                }
+                // 成交量异常没有可修正的价格，原样接收
+                ErrCode::TradeinfoTooMuchZero | ErrCode::Suspension => return Ok(true),
+                _ => return Ok(false),
+            }
+            match self.check_klu(klu) {
+                Some(next_err) => err = next_err,
+                None => return Ok(true),
+            }
+        }
+    }
+
+    /// 按 `errcode` 对应的问题修正价格
+    fn clamp_klu(&self, klu: &mut KLineUnit, errcode: ErrCode) {
+        match errcode {
+            ErrCode::PriceBelowZero => {
+                klu.open = klu.open.max(0.0);
+                klu.high = klu.high.max(0.0);
+                klu.low = klu.low.max(0.0);
+                klu.close = klu.close.max(0.0);
+            }
+            ErrCode::KlDataInvalid => {
+                let pre_close = self.klus.last().expect("spike needs a previous klu").close;
+                let rate = self.config.max_price_change_rate;
+                let (lower, upper) = (pre_close * (1.0 - rate), pre_close * (1.0 + rate));
+                klu.open = klu.open.clamp(lower, upper);
+                klu.high = klu.high.clamp(lower, upper);
+                klu.low = klu.low.clamp(lower, upper);
+                klu.close = klu.close.clamp(lower, upper);
+            }
+            ErrCode::PriceNotOnTick => {
+                let symbol = self.symbol.as_ref().expect("tick check needs symbol info");
+                for price in [&mut klu.open, &mut klu.high, &mut klu.low, &mut klu.close] {
+                    *price = symbol.round_price(*price);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn check_klu(&self, klu: &KLineUnit) -> Option<ChanException> {
+        if klu.low < 0.0 {
+            return Some(ChanException::new(
+                tr(MsgKey::PriceBelowZero, &[&klu.time, &klu.low]),
+                ErrCode::PriceBelowZero,
+            ));
+        }
+        if let Some(symbol) = &self.symbol {
+            let prices = [klu.open, klu.high, klu.low, klu.close];
+            if let Some(price) = prices.into_iter().find(|&p| !symbol.is_valid_price(p)) {
+                return Some(ChanException::new(
+                    tr(
+                        MsgKey::PriceNotOnTick,
+                        &[&klu.time, &price, &symbol.tick_size],
+                    ),
+                    ErrCode::PriceNotOnTick,
+                ));
+            }
+        }
+        let last_klu = self.klus.last()?;
+        // 逐笔数据同一秒内可以有多笔
+        let same_time_ok = self.kl_type == Some(KLineType::KTick);
+        if klu.time < last_klu.time || (klu.time == last_klu.time && !same_time_ok) {
+            return Some(ChanException::new(
+                tr(MsgKey::TimeNotMonotonous, &[&klu.time, &last_klu.time]),
+                ErrCode::KlNotMonotonous,
+            ));
+        }
+        let pre_close = last_klu.close;
+        let change_rate = (klu.high / pre_close - 1.0)
+            .abs()
+            .max((klu.low / pre_close - 1.0).abs());
+        if pre_close > 0.0 && change_rate > self.config.max_price_change_rate {
+            return Some(ChanException::new(
+                tr(
+                    MsgKey::PriceJump,
+                    &[
+                        &klu.time,
+                        &format!("{:.2}", change_rate * 100.0),
+                        &pre_close,
+                    ],
+                ),
+                ErrCode::KlDataInvalid,
+            ));
+        }
+        if klu.trade_info.volume == Some(0.0) {
+            if is_halt_bar(klu, pre_close) {
+                return Some(ChanException::new(
+                    tr(MsgKey::Suspension, &[&klu.time]),
+                    ErrCode::Suspension,
+                ));
+            }
+            let zero_cnt = 1 + self
+                .klus
+                .iter()
+                .rev()
+                .take(self.config.max_zero_volume_cnt)
+                .take_while(|klu| klu.trade_info.volume == Some(0.0))
+                .count();
+            if zero_cnt > self.config.max_zero_volume_cnt {
+                return Some(ChanException::new(
+                    tr(MsgKey::TooMuchZeroVolume, &[&klu.time, &zero_cnt]),
+                    ErrCode::TradeinfoTooMuchZero,
+                ));
+            }
+        }
+        None
+    }
+
+    /// 批量导入一段连续的K线，预先分配好空间；中途出错时停止，已导入的保留，返回已导入的根数和错误
+    pub fn add_klu_slice(&mut self, bars: &[KluInput]) -> Result<(), (usize, ChanException)> {
+        self.klus.reserve(bars.len());
+        for (i, bar) in bars.iter().enumerate() {
+            bar.to_klu(false)
+                .and_then(|klu| self.add_single_klu(klu))
+                .map_err(|e| (i, e))?;
+        }
+        Ok(())
+    }
+
+    /// 不做校验直接合并，回退重放和合并已校验过的KLU时使用
+    fn add_klu_unchecked(&mut self, mut klu: KLineUnit) -> ChanResult<()> {
+        klu.idx = self.klus.len();
+        if klu.kl_type.is_none() {
+            klu.kl_type = self.kl_type;
+        }
+        let klu_idx = klu.idx;
+        let begin = self.profiler.is_some().then(Instant::now);
+        // KLU先入列再更新分形，判断缺口时要用到最新的KLU
+        let mut fx_action: Option<fn(&mut Self)> = None;
+        match self.lst.last_mut() {
+            None => {
+                klu.klc = Some(0);
+                self.lst.push(KLine::new(&klu, 0, KLineDir::Up));
+            }
+            Some(last_kl) => {
+                let (high, low) = (last_kl.high(), last_kl.low());
+                let dir = last_kl.try_add_klu(
+                    &klu,
+                    self.config.combine_dir,
+                    self.config.equal_extreme,
+                )?;
+                if dir == KLineDir::Combine {
+                    klu.klc = Some(last_kl.idx);
+                    if (high, low) != (last_kl.high(), last_kl.low()) && self.lst.len() >= 3 {
+                        // 最后一根KLC高低点变了，倒数第二根的分形要重新判断
+                        fx_action = Some(Self::refresh_last_fx);
+                    }
+                } else {
+                    // 不需要合并K线
+                    let idx = self.lst.len();
+                    klu.klc = Some(idx);
+                    self.lst.push(KLine::new(&klu, idx, dir));
+                    if self.lst.len() >= 3 {
+                        fx_action = Some(Self::update_last_fx);
+                    }
+                }
+            }
+        }
+        if let Some(macd) = self.macd.as_mut() {
+            klu.trade_info.macd = Some(macd.add(klu.close));
+        }
+        self.klus.push(klu);
+        if let (Some(profiler), Some(begin)) = (self.profiler.as_mut(), begin) {
+            profiler.record(klu_idx, Phase::KlcMerge, begin);
+        }
+        if let Some(action) = fx_action {
+            let begin = self.profiler.is_some().then(Instant::now);
+            action(self);
+            if let (Some(profiler), Some(begin)) = (self.profiler.as_mut(), begin) {
+                profiler.record(klu_idx, Phase::FxDetect, begin);
+            }
+        }
+        Ok(())
+    }
+
+    /// 用最后三根KLC更新倒数第二根的分形
+    fn update_last_fx(&mut self) {
+        let n = self.lst.len();
+        let gap = self.fx_gap(n - 2);
+        let fx_conf = self.config.fx_conf;
+        let (head, tail) = self.lst.split_at_mut(n - 2);
+        let (cur, next) = tail.split_at_mut(1);
+        calc_fx(&mut cur[0], &head[n - 3], &next[0], &fx_conf, &self.fx_list);
+        self.fx_list.update(&cur[0], gap);
+    }
+
+    /// 最后一根KLC因合并而改变后，按最新的三根KLC重算倒数第二根的分形
+    fn refresh_last_fx(&mut self) {
+        let n = self.lst.len();
+        let gap = self.fx_gap(n - 2);
+        let fx_conf = self.config.fx_conf;
+        let (head, tail) = self.lst.split_at_mut(n - 2);
+        let (cur, next) = tail.split_at_mut(1);
+        cur[0].set_fx(FxType::Unknown);
+        calc_fx(&mut cur[0], &head[n - 3], &next[0], &fx_conf, &self.fx_list);
+        self.fx_list.refresh(&cur[0], gap);
+    }
+
+    /// 第 `klc_idx` 根KLC与左右相邻KLC之间的缺口情况，没有相邻KLC的一侧视为无缺口
+    fn fx_gap(&self, klc_idx: usize) -> FxGap {
+        FxGap {
+            with_pre: klc_idx > 0 && self.has_gap_with_next(klc_idx - 1),
+            with_next: klc_idx + 1 < self.lst.len() && self.has_gap_with_next(klc_idx),
+        }
+    }
+
+    pub fn get_klu_max_high(&self, klc: &KLine) -> f64 {
+        self.klc_units(klc)
+            .iter()
+            .map(|klu| klu.high)
+            .fold(f64::NEG_INFINITY, f64::max)
+    }
+
+    pub fn get_klu_min_low(&self, klc: &KLine) -> f64 {
+        self.klc_units(klc)
+            .iter()
+            .map(|klu| klu.low)
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    /// 第 `klc_idx` 根KLC与下一根之间是否有缺口，相同也算重叠，也就是没有gap
+    pub fn has_gap_with_next(&self, klc_idx: usize) -> bool {
+        let cur = &self.lst[klc_idx];
+        let next = self.lst.get(klc_idx + 1).expect("klc has no next");
+        !has_overlap(
+            self.get_klu_min_low(cur),
+            self.get_klu_max_high(cur),
+            self.get_klu_min_low(next),
+            self.get_klu_max_high(next),
+            true,
+        )
+    }
+
+    /// `last_end` 到 `klc_idx` 之间的KLC跨度，`gap_as_kl` 打开时每个缺口算作一根虚拟KLC
+    pub fn get_klc_span(&self, klc_idx: usize, last_end: usize, bi_conf: &BiConfig) -> usize {
+        let mut span = klc_idx - last_end;
+        if !bi_conf.gap_as_kl {
+            return span;
+        }
+        if span >= 4 {
+            // 加速运算，如果span需要真正精确的值，需要去掉这一行
+            return span;
+        }
+        span += (last_end..klc_idx)
+            .filter(|&idx| self.has_gap_with_next(idx))
+            .count();
+        span
+    }
+
+    pub fn klu_iter(&self, klc_begin_idx: usize) -> impl Iterator<Item = &KLineUnit> {
+        let begin = self
+            .lst
+            .get(klc_begin_idx)
+            .map_or(self.klus.len(), |klc| *klc.klu_range().start());
+        self.klus[begin..].iter()
+    }
+
+    /// 从最后一根KLU往前遍历
+    pub fn klu_iter_rev(&self) -> impl Iterator<Item = &KLineUnit> {
+        self.klus.iter().rev()
+    }
+
+    /// 时间戳落在 [begin_ts, end_ts] 内的KLU，KLU按时间递增，二分定位
+    pub fn klu_range(&self, begin_ts: i64, end_ts: i64) -> &[KLineUnit] {
+        let begin = self.klus.partition_point(|klu| klu.time.ts() < begin_ts);
+        let end = self.klus.partition_point(|klu| klu.time.ts() <= end_ts);
+        &self.klus[begin..end.max(begin)]
+    }
+
+    /// 分形的顶点（底点）所在的KLU
+    pub fn fx_peak_klu(&self, fx: &Fx) -> ChanResult<&KLineUnit> {
+        self.lst[fx.klc_idx].get_peak_klu(&self.klus, fx.fx_type == FxType::Top)
+    }
+
+    /// 分形的量能集中度：顶点（底点）KLU的成交量 / 分形三根KLC内KLU的平均成交量
+    ///
+    /// 大于1说明成交集中在极值处，缺成交量数据或平均成交量为0时返回 None
+    pub fn fx_volume_strength(&self, fx: &Fx) -> Option<f64> {
+        let peak_volume = self.fx_peak_klu(fx).ok()?.trade_info.volume?;
+        let begin = *self
+            .lst
+            .get(fx.klc_idx.checked_sub(1)?)?
+            .klu_range()
+            .start();
+        let end = *self.lst.get(fx.klc_idx + 1)?.klu_range().end();
+        let volumes = self.klus[begin..=end]
+            .iter()
+            .map(|klu| klu.trade_info.volume)
+            .collect::<Option<Vec<f64>>>()?;
+        let avg = volumes.iter().sum::<f64>() / volumes.len() as f64;
+        (avg > 0.0).then(|| peak_volume / avg)
+    }
+
+    /// 在副本上追加假想的后续KLU，返回因此而确定的分形（原本未确定的和新出现的），自身不变
+    ///
+    /// 副本只含 `speculative_tail` 复制的尾部，返回前把分形下标换回本列表的
+    pub fn speculate(&self, klus: impl IntoIterator<Item = KLineUnit>) -> ChanResult<Vec<Fx>> {
+        let (mut kl_list, klc_begin) = self.speculative_tail();
+        let fx_offset = self.fx_list.len() - kl_list.fx_list.len();
+        for klu in klus {
+            kl_list.add_single_klu(klu)?;
+        }
+        Ok(kl_list
+            .fx_list
+            .iter()
+            .filter(|fx| fx.is_sure)
+            .filter(|fx| !self.fx_list.get_by_id(fx.id).is_some_and(|fx| fx.is_sure))
+            .map(|fx| Fx {
+                idx: fx.idx + fx_offset,
+                klc_idx: fx.klc_idx + klc_begin,
+                ..fx.clone()
+            })
+            .collect())
+    }
+
+    /// 复制追加KLU时还会用到的尾部，返回副本和它第一根KLC在本列表中的下标
+    ///
+    /// 已确定的分形不会再变，从最后一个已确定分形的前一根KLC起复制就够了（至少留最后三根），
+    /// 下标同 `trim_before` 一样重排；性能统计和提醒规则不复制
+    fn speculative_tail(&self) -> (Self, usize) {
+        let anchor = self
+            .fx_list
+            .iter()
+            .rev()
+            .find(|fx| fx.is_sure)
+            .or_else(|| self.fx_list.get(0))
+            .map_or(self.lst.len(), |fx| fx.klc_idx);
+        let klc_begin = anchor
+            .min(self.lst.len().saturating_sub(3))
+            .saturating_sub(1);
+        let klu_begin = self
+            .lst
+            .get(klc_begin)
+            .map_or(0, |klc| *klc.klu_range().start());
+        let mut tail = Self::with_config(self.kl_type, self.config.clone());
+        tail.symbol = self.symbol.clone();
+        tail.macd = self.macd.clone();
+        tail.fx_list = self.fx_list.tail_from_klc(klc_begin);
+        tail.lst = self.lst[klc_begin..].to_vec();
+        for klc in &mut tail.lst {
+            klc.shift_idx(klc_begin, klu_begin);
+        }
+        tail.klus = self.klus[klu_begin..].to_vec();
+        for klu in &mut tail.klus {
+            klu.idx -= klu_begin;
+            klu.klc = klu.klc.map(|idx| idx - klc_begin);
+        }
+        (tail, klc_begin)
+    }
+
+    /// 只用时间不晚于 `ts` 的KLU重放一遍，得到当时的状态（包括哪些分形还没确定）
+    ///
+    /// 重放的是已入列的KLU：被 `on_invalid_bar` 丢弃的不再出现，修正过的按修正后的值；
+    /// 次级别关联和提醒规则不保留。`trim_before` 丢弃过KLU的列表缺少开头的历史，
+    /// 回补合并或回退重算过的列表分形编号与重放不一致，这两种情况直接返回错误
+    pub fn reconstruct_at(&self, ts: i64) -> ChanResult<Self> {
+        if self.trimmed_klu_cnt > 0 {
+            return Err(ChanException::new(
+                tr(MsgKey::ReconstructTrimmed, &[&self.trimmed_klu_cnt]),
+                ErrCode::CommonError,
+            ));
+        }
+        if self.rebuilt {
+            return Err(ChanException::new(
+                tr(MsgKey::ReconstructRebuilt, &[]),
+                ErrCode::CommonError,
+            ));
+        }
+        let end = self.klus.partition_point(|klu| klu.time.ts() <= ts);
+        let mut kl_list = Self::with_config(self.kl_type, self.config.clone());
+        for klu in &self.klus[..end] {
+            let mut klu = klu.clone();
+            klu.sub_kl_list.clear();
+            klu.sup_kl = None;
+            kl_list.add_single_klu(klu)?;
+        }
+        Ok(kl_list)
+    }
+
+    /// 把级别、配置、全部KLU、KLC和分形打包成带版本号和校验和的二进制快照；
+    /// 次级别关联、提醒规则、标的属性和耗时统计不在快照里
+    pub fn snapshot(&self) -> ChanSnapshot {
+        snapshot::encode(
+            self.kl_type,
+            &self.config,
+            ListState {
+                invalid_bar_cnt: self.invalid_bar_cnt,
+                trimmed_klu_cnt: self.trimmed_klu_cnt,
+                rebuilt: self.rebuilt,
+            },
+            &self.klus,
+            &self.lst,
+            &self.fx_list,
+        )
+    }
+
+    /// 由 `snapshot` 的结果恢复，KLC和分形（包括编号）原样装回而不是重放KLU，`trim_before` 之后的列表也能还原；
+    /// 版本号、校验和或内容不对时返回 `ErrCode::SnapshotErr`
+    pub fn restore(snapshot: &ChanSnapshot) -> ChanResult<Self> {
+        let data = snapshot::decode(snapshot)?;
+        let mut kl_list = Self::with_config(data.kl_type, data.config);
+        kl_list.klus = data.klus;
+        kl_list.lst = data.lst;
+        kl_list.fx_list = data.fx_list;
+        kl_list.invalid_bar_cnt = data.state.invalid_bar_cnt;
+        kl_list.trimmed_klu_cnt = data.state.trimmed_klu_cnt;
+        kl_list.rebuilt = data.state.rebuilt;
+        kl_list.resume_macd();
+        if !kl_list.check_invariants().is_empty() {
+            return Err(snapshot::corrupted("inconsistent klc or fx"));
+        }
+        Ok(kl_list)
+    }
+
+    /// 前后各 `n` 根KLC的文本，中间省略，KLC很多时打印用
+    pub fn summary_str(&self, n: usize) -> String {
+        let shown = self.lst.len().min(2 * n);
+        let mut out = String::with_capacity(shown * 80 + 32);
+        self.write_klcs(&mut out, n)
+            .expect("writing to String never fails");
+        out
+    }
+
+    /// 逐根KLC直接写入 `w`，`n` 为前后各保留的根数
+    fn write_klcs(&self, w: &mut impl Write, n: usize) -> fmt::Result {
+        let len = self.lst.len();
+        for (i, klc) in self.lst.iter().enumerate() {
+            if len > 2 * n && i == n {
+                writeln!(w, "... {} more ...", len - 2 * n)?;
+            }
+            if len <= 2 * n || i < n || i >= len - n {
+                writeln!(w, "{klc}")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 检查KLU、KLC、分形之间的相互引用是否一致，合并/拆分等操作之后可用来自检，返回所有不一致项
+    pub fn check_invariants(&self) -> Vec<InvariantViolation> {
+        use InvariantViolation::*;
+        let mut res = Vec::new();
+        let same_time_ok = self.kl_type == Some(KLineType::KTick);
+        for (idx, klu) in self.klus.iter().enumerate() {
+            if klu.idx != idx {
+                res.push(KluIdx {
+                    klu_idx: idx,
+                    found: klu.idx,
+                });
+            }
+            if idx > 0 {
+                let pre_time = self.klus[idx - 1].time;
+                if klu.time < pre_time || (klu.time == pre_time && !same_time_ok) {
+                    res.push(KluTimeOrder { klu_idx: idx });
+                }
+            }
+        }
+        let mut covered = 0;
+        for (idx, klc) in self.lst.iter().enumerate() {
+            if klc.idx != idx {
+                res.push(KlcIdx {
+                    klc_idx: idx,
+                    found: klc.idx,
+                });
+            }
+            let range = klc.klu_range();
+            if *range.start() != covered || *range.end() >= self.klus.len() {
+                res.push(KlcNotContiguous { klc_idx: idx });
+                covered = covered.max(range.end() + 1);
+                continue;
+            }
+            for klu_idx in range {
+                if self.klus[klu_idx].klc != Some(idx) {
+                    res.push(KluKlc {
+                        klu_idx,
+                        klc_idx: idx,
+                        found: self.klus[klu_idx].klc,
+                    });
+                }
+            }
+            covered = klc.klu_range().end() + 1;
+        }
+        if covered != self.klus.len() {
+            res.push(KluNotCovered {
+                klu_cnt: self.klus.len(),
+                covered,
+            });
+        }
+
+        let mut pre: Option<&Fx> = None;
+        for (idx, fx) in self.fx_list.iter().enumerate() {
+            if fx.idx != idx {
+                res.push(FxIdx {
+                    fx_idx: idx,
+                    found: fx.idx,
+                });
+            }
+            if self
+                .lst
+                .get(fx.klc_idx)
+                .is_none_or(|klc| klc.fx() != fx.fx_type)
+            {
+                res.push(FxKlc {
+                    fx_id: fx.id,
+                    klc_idx: fx.klc_idx,
+                });
+            }
+            if pre.is_some_and(|pre| pre.klc_idx >= fx.klc_idx || pre.id >= fx.id) {
+                res.push(FxOrder { fx_id: fx.id });
+            }
+            if fx.is_sure != (fx.klc_idx + 2 < self.lst.len()) {
+                res.push(FxSure {
+                    fx_id: fx.id,
+                    is_sure: fx.is_sure,
+                });
+            }
+            pre = Some(fx);
+        }
+        for (idx, klc) in self.lst.iter().enumerate() {
+            if klc.fx() != FxType::Unknown && self.fx_list.get_by_klc(idx).is_none() {
+                res.push(FxMissing { klc_idx: idx });
+            }
+        }
+        res
+    }
+
+    /// 各类元素的数量等统计信息
+    pub fn summary(&self) -> KLineSummary {
+        let fx_cnt = |fx_type: FxType| {
+            self.fx_list
+                .iter()
+                .filter(|fx| fx.fx_type == fx_type)
+                .count()
+        };
+        KLineSummary {
+            klu_cnt: self.klus.len(),
+            klc_cnt: self.lst.len(),
+            top_fx_cnt: fx_cnt(FxType::Top),
+            bottom_fx_cnt: fx_cnt(FxType::Bottom),
+            unsure_fx_cnt: self.fx_list.iter().filter(|fx| !fx.is_sure).count(),
+            invalid_bar_cnt: self.invalid_bar_cnt,
+            halted_klu_cnt: self.klus.iter().filter(|klu| klu.is_halted).count(),
+            avg_klc_len: if self.lst.is_empty() {
+                0.0
+            } else {
+                self.klus.len() as f64 / self.lst.len() as f64
+            },
+            time_range: self
+                .klus
+                .first()
+                .zip(self.klus.last())
+                .map(|(first, last)| (first.time, last.time)),
+        }
+    }
+
+    /// 连续停牌KLU的起止时间
+    pub fn halted_ranges(&self) -> Vec<(CTime, CTime)> {
+        self.klus
+            .chunk_by(|a, b| a.is_halted == b.is_halted)
+            .filter(|run| run[0].is_halted)
+            .map(|run| (run[0].time, run[run.len() - 1].time))
+            .collect()
+    }
+
+    /// KLC里是否有停牌KLU
+    pub fn is_klc_halted(&self, klc: &KLine) -> bool {
+        self.klc_units(klc).iter().any(|klu| klu.is_halted)
+    }
+
+    /// 与 [begin_ts, end_ts] 有重叠的KLC，KLC按时间递增，二分定位
+    pub fn query_range(&self, begin_ts: i64, end_ts: i64) -> &[KLine] {
+        chan_element::query_range(&self.lst, begin_ts, end_ts)
+    }
+
+    /// 时间戳 `ts` 所在的KLU及其所属的KLC、分形
+    ///
+    /// KLU的时间是结束时间，所以取第一根时间不早于 `ts` 的KLU；`ts` 晚于最后一根KLU时返回 None
+    pub fn element_at(&self, ts: i64) -> Option<ElementsAt<'_>> {
+        let klu = self
+            .klus
+            .get(self.klus.partition_point(|klu| klu.time.ts() < ts))?;
+        let klc = self.lst.get(klu.klc()?)?;
+        Some(ElementsAt {
+            klu,
+            klc,
+            fx: self.fx_list.get_by_klc(klc.idx),
+        })
+    }
+
+    /// 回退到只剩前 `klu_cnt` 根KLU的状态，返回被移除的KLU
+    ///
+    /// 第一根被移除KLU所在的KLC整根拆掉，其中仍需保留的KLU重新走一遍合并流程
+    fn rollback_to(&mut self, klu_cnt: usize) -> ChanResult<Vec<KLineUnit>> {
+        let Some(klc_idx) = self.klus.get(klu_cnt).and_then(KLineUnit::klc) else {
+            return Ok(Vec::new());
+        };
+        let klc_begin = *self.lst[klc_idx].klu_range().start();
+        let mut removed = self.klus.split_off(klc_begin);
+        self.lst.truncate(klc_idx);
+        self.fx_list.truncate_to_klc_cnt(klc_idx);
+        if let Some(last_klc) = self.lst.last_mut() {
+            // 最后一根KLC的分形依赖后一根，已经不成立
+            last_klc.set_fx(FxType::Unknown);
+        }
+        let dropped = removed.split_off(klu_cnt - klc_begin);
+        // 回退掉的分形已经占用过编号
+        self.rebuilt = true;
+        self.resume_macd();
+        for klu in removed {
+            self.add_klu_unchecked(klu)?;
+        }
+        Ok(dropped)
+    }
+
+    /// 只保留前 `klu_cnt` 根KLU，多级别联立挂接失败时撤销刚加入的KLU
+    pub(crate) fn truncate_klus(&mut self, klu_cnt: usize) -> ChanResult<()> {
+        self.rollback_to(klu_cnt).map(|_| ())
+    }
+
+    /// MACD 从当前最后一根KLU之后接着算，回退或清空后重放KLU之前调用
+    fn resume_macd(&mut self) {
+        let last = self.klus.last().and_then(|klu| klu.trade_info.macd);
+        if let Some(macd) = self.macd.as_mut() {
+            macd.reset_to(last);
+        }
+    }
+
+    /// 合并另一段同级别的K线
+    ///
+    /// `other` 整段晚于本列表时视为新下载的尾部，直接接上；整段早于本列表时视为回补更早的历史，
+    /// 以 `other` 为开头、接上本列表整体重算；两段时间有重叠时返回 `KlNotMonotonous`。
+    /// 边界处的KLC合并和分形都会重新计算，跨级别的挂接关系全部清空，需要重新建立
+    pub fn merge(&mut self, other: KLineList) -> ChanResult<()> {
+        if let (Some(lv), Some(other_lv)) = (self.kl_type, other.kl_type) {
+            if lv != other_lv {
+                return Err(ChanException::new(
+                    tr(
+                        MsgKey::MergeLevelMismatch,
+                        &[&format!("{other_lv:?}"), &format!("{lv:?}")],
+                    ),
+                    ErrCode::ParaError,
+                ));
+            }
+        }
+        let mut other_klus = other.klus;
+        for klu in &mut other_klus {
+            klu.sub_kl_list.clear();
+            klu.sup_kl = None;
+        }
+        let (Some(first), Some(last), Some(other_first), Some(other_last)) = (
+            self.klus.first().map(|klu| klu.time),
+            self.klus.last().map(|klu| klu.time),
+            other_klus.first().map(|klu| klu.time),
+            other_klus.last().map(|klu| klu.time),
+        ) else {
+            if self.klus.is_empty() {
+                self.resume_macd();
+                for klu in other_klus {
+                    self.add_klu_unchecked(klu)?;
+                }
+            }
+            return Ok(());
+        };
+
+        // 逐笔数据同一秒内可以有多笔，边界上允许时间相等
+        let same_time_ok = self.kl_type == Some(KLineType::KTick);
+        let is_after =
+            |later: CTime, earlier: CTime| later > earlier || (same_time_ok && later == earlier);
+        if is_after(other_first, last) {
+            for klu in other_klus {
+                self.add_klu_unchecked(klu)?;
+            }
+        } else if is_after(first, other_last) {
+            let mut tail = std::mem::take(&mut self.klus);
+            for klu in &mut tail {
+                klu.sub_kl_list.clear();
+                klu.sup_kl = None;
+            }
+            self.lst.clear();
+            self.fx_list.clear();
+            self.resume_macd();
+            self.rebuilt = true;
+            for klu in other_klus.into_iter().chain(tail) {
+                self.add_klu_unchecked(klu)?;
+            }
+        } else {
+            return Err(ChanException::new(
+                tr(
+                    MsgKey::MergeOverlap,
+                    &[&other_first, &other_last, &first, &last],
+                ),
+                ErrCode::KlNotMonotonous,
+            ));
+        }
+        Ok(())
+    }
+
+    /// 丢弃结束时间早于 `ts` 的KLC及其KLU，剩余元素的下标整体前移，返回丢弃的KLU数量
+    ///
+    /// 跨过 `ts` 的KLC会整根保留；其他级别通过 `sup_kl`/`sub_kl_list` 指向本级别的下标需要调用方自行调整
+    pub fn trim_before(&mut self, ts: i64) -> usize {
+        let klc_cnt = self.lst.partition_point(|klc| klc.time_end().ts() < ts);
+        if klc_cnt == 0 {
+            return 0;
+        }
+        let klu_cnt = *self.lst[klc_cnt - 1].klu_range().end() + 1;
+        self.lst.drain(..klc_cnt);
+        self.fx_list.trim_before_klc(klc_cnt);
+        self.klus.drain(..klu_cnt);
+        for klc in &mut self.lst {
+            klc.shift_idx(klc_cnt, klu_cnt);
+        }
+        for klu in &mut self.klus {
+            klu.idx -= klu_cnt;
+            klu.klc = klu.klc.map(|idx| idx - klc_cnt);
+        }
+        self.trimmed_klu_cnt += klu_cnt;
+        klu_cnt
+    }
+
+    /// 最后 n 根KLU，不足 n 根时返回全部
+    pub fn last_n_klu(&self, n: usize) -> &[KLineUnit] {
+        &self.klus[self.klus.len().saturating_sub(n)..]
+    }
+
+    /// 按 `stitch_sub_level` 建立的挂接关系，依次给出每根KLU及其下挂的次级别KLU
+    ///
+    /// 子KLU总是连续挂接的，所以直接切片；尚未挂接子KLU的KLU对应空切片
+    pub fn iter_with_sub<'a>(
+        &'a self,
+        sub: &'a KLineList,
+    ) -> impl Iterator<Item = (&'a KLineUnit, &'a [KLineUnit])> + 'a {
+        self.klus.iter().map(move |klu| {
+            let children = match (klu.sub_kl_list.first(), klu.sub_kl_list.last()) {
+                (Some(&first), Some(&last)) => &sub.klus[first..=last],
+                _ => &[],
+            };
+            (klu, children)
+        })
+    }
+
+    /// 按时间包含关系把次级别 `sub` 中尚未挂接的KLU挂到本级别KLU下，返回新挂接的数量
+    ///
+    /// 天级别以下KLU的时间是结束时间，天级别及以上按整天算（同 chan.py 的 auto 时间），
+    /// 所以子KLU归属于第一根覆盖到它的父KLU；父级别尚未覆盖到的子KLU留待下次调用。
+    /// 落在子级别时间范围内、已被后续子KLU越过却没有任何子KLU的父KLU视为对不齐，
+    /// 条数达到 `max_kl_misalgin_cnt` 时返回 `KlDataNotAlign`
+    pub fn stitch_sub_level(
+        &mut self,
+        sub: &mut KLineList,
+        max_kl_misalgin_cnt: usize,
+    ) -> ChanResult<usize> {
+        if let (Some(lv), Some(sub_lv)) = (self.kl_type, sub.kl_type) {
+            if sub_lv >= lv {
+                return Err(ChanException::new(
+                    tr(
+                        MsgKey::SubLevelNotLower,
+                        &[&format!("{sub_lv:?}"), &format!("{lv:?}")],
+                    ),
+                    ErrCode::ParaError,
+                ));
+            }
+        }
+        let day_level = self.kl_type.is_some_and(|lv| !kltype_lt_day(lv));
+        let cover_ts = |klu: &KLineUnit| parent_cover_ts(klu.time, day_level);
+        let mut linked = 0;
+        for child in sub.klus.iter_mut().filter(|klu| klu.sup_kl.is_none()) {
+            let parent_idx = self
+                .klus
+                .partition_point(|klu| cover_ts(klu) < child.time.ts());
+            let Some(parent) = self.klus.get_mut(parent_idx) else {
+                break;
+            };
+            parent.add_children(child.idx);
+            child.set_parent(parent_idx);
+            linked += 1;
+        }
+
+        let (Some(first_sub), Some(last_sub)) = (sub.klus.first(), sub.klus.last()) else {
+            return Ok(linked);
+        };
+        let (first_sub_ts, last_sub_ts) = (first_sub.time.ts(), last_sub.time.ts());
+        // 子级别历史开始之前的父KLU本来就不会有子KLU，不算对不齐
+        let begin = self
+            .klus
+            .partition_point(|klu| cover_ts(klu) < first_sub_ts);
+        let misalign_cnt = self.klus[begin..]
+            .iter()
+            .take_while(|klu| cover_ts(klu) < last_sub_ts)
+            .filter(|klu| klu.sub_kl_list.is_empty())
+            .count();
+        if misalign_cnt >= max_kl_misalgin_cnt {
+            return Err(ChanException::new(
+                tr(MsgKey::SubLevelNotAlign, &[&max_kl_misalgin_cnt]),
+                ErrCode::KlDataNotAlign,
+            ));
+        }
+        Ok(linked)
+    }
+
+    /// 已挂接到父级别的KLU根数；挂接按时间顺序进行，未挂接的总是末尾一段
+    pub(crate) fn stitched_cnt(&self) -> usize {
+        self.klus
+            .iter()
+            .rposition(|klu| klu.sup_kl.is_some())
+            .map_or(0, |idx| idx + 1)
+    }
+
+    /// 撤销 `sub` 中第 `sub_begin` 根及之后KLU的挂接
+    pub(crate) fn unstitch_sub_level(&mut self, sub: &mut KLineList, sub_begin: usize) {
+        for child in sub.klus.iter_mut().skip(sub_begin) {
+            child.sup_kl = None;
+        }
+        for parent in self.klus.iter_mut().rev() {
+            if parent
+                .sub_kl_list
+                .last()
+                .is_some_and(|&last| last < sub_begin)
+            {
+                break;
+            }
+            parent.sub_kl_list.retain(|&child| child < sub_begin);
+        }
+    }
+}
+
+/// 父KLU覆盖到的最晚时间戳，天级别及以上取当天 23:59
+fn parent_cover_ts(time: CTime, day_level: bool) -> i64 {
+    if day_level {
+        CTime::new(time.year, time.month, time.day, 0, 0, 0, true).ts()
+    } else {
+        time.ts()
+    }
+}
+
+/// 零成交、一字且收盘价与上一根相同，视为停牌
+fn is_halt_bar(klu: &KLineUnit, pre_close: f64) -> bool {
+    klu.trade_info.volume == Some(0.0) && klu.high == klu.low && klu.close == pre_close
+}
+
+/// 按 `fx_conf` 的口径计算 `cur` 的分形，`fx_list` 中已有的分形用于检查最小间隔
+fn calc_fx(cur: &mut KLine, pre: &KLine, next: &KLine, fx_conf: &FxConfig, fx_list: &FxList) {
+    if fx_conf.allow_equal {
+        // allow_top_equal 一次只放宽一端，顶、底各判一次
+        cur.update_fx(pre, next, true, Some(1));
+        if cur.fx() == FxType::Unknown {
+            cur.update_fx(pre, next, true, Some(-1));
+        }
+    } else {
+        cur.update_fx(pre, next, !fx_conf.strict, None);
+    }
+    if cur.fx() != FxType::Unknown && fx_conf.min_klc_gap > 0 {
+        let too_close = fx_list
+            .iter()
+            .rev()
+            .find(|fx| fx.klc_idx < cur.idx)
+            .is_some_and(|fx| cur.idx - fx.klc_idx < fx_conf.min_klc_gap);
+        if too_close {
+            cur.set_fx(FxType::Unknown);
+        }
+    }
+}
+
+impl fmt::Display for KLineList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_klcs(f, usize::MAX / 2)
+    }
+}
+
+impl Index<usize> for KLineList {
+    type Output = KLine;
+
+    fn index(&self, idx: usize) -> &KLine {
+        &self.lst[idx]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chan_config::{EqualExtremePolicy, MacdConfig};
+    use crate::combiner::kline_combiner::Combiner;
+    use crate::common::ctime::CTime;
+    use crate::kline::trade_info::TradeInfo;
+    use crate::testkit::MarketGen;
+
+    fn feed(kl_list: &mut KLineList, bars: &[(f64, f64)]) {
+        for (i, &(high, low)) in bars.iter().enumerate() {
+            let t = CTime::new(2024, 1, 1 + i as u32, 0, 0, 0, false);
+            let klu = KLineUnit::new(t, low, high, low, high, TradeInfo::default(), false).unwrap();
+            kl_list.add_single_klu(klu).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_add_single_klu_combine() {
+        let mut kl_list = KLineList::new(Some(KLineType::KDay));
+        feed(
+            &mut kl_list,
+            &[
+                (10.0, 8.0),
+                (11.0, 9.0),
+                (10.5, 9.5),
+                (9.0, 7.0),
+                (8.5, 7.5),
+            ],
+        );
+        assert_eq!(kl_list.len(), 3);
+        assert_eq!(kl_list.klu_cnt(), 5);
+        assert_eq!(kl_list[1].klu_range(), 1..=2);
+        assert_eq!((kl_list[1].high(), kl_list[1].low()), (11.0, 9.5));
+        assert_eq!(kl_list[2].dir(), KLineDir::Down);
+        assert_eq!(kl_list[1].fx(), FxType::Top);
+        assert_eq!((kl_list[2].high(), kl_list[2].low()), (8.5, 7.0));
+        assert_eq!(kl_list.klu(2).unwrap().klc(), Some(1));
+        assert_eq!(kl_list.klu_iter(1).count(), 4);
+        assert_eq!(kl_list.klc_units(&kl_list[2]).len(), 2);
+    }
+
+    #[test]
+    fn test_gap_as_kl_span() {
+        let mut kl_list = KLineList::new(Some(KLineType::KDay));
+        // 第1、2根之间跳空
+        feed(
+            &mut kl_list,
+            &[(10.0, 8.0), (9.0, 7.0), (6.0, 5.0), (7.0, 5.5)],
+        );
+        assert_eq!(kl_list.len(), 4);
+        assert!(!kl_list.has_gap_with_next(0));
+        assert!(kl_list.has_gap_with_next(1));
+
+        let mut bi_conf = BiConfig {
+            gap_as_kl: false,
+            ..Default::default()
+        };
+        assert_eq!(kl_list.get_klc_span(3, 0, &bi_conf), 3);
+        bi_conf.gap_as_kl = true;
+        assert_eq!(kl_list.get_klc_span(3, 0, &bi_conf), 4);
+    }
+
+    #[test]
+    fn test_klu_iters() {
+        let mut kl_list = KLineList::new(Some(KLineType::KDay));
+        feed(
+            &mut kl_list,
+            &[(10.0, 8.0), (11.0, 9.0), (10.5, 9.5), (9.0, 7.0)],
+        );
+        let rev: Vec<usize> = kl_list.klu_iter_rev().map(|klu| klu.idx()).collect();
+        assert_eq!(rev, vec![3, 2, 1, 0]);
+
+        let begin_ts = CTime::new(2024, 1, 2, 0, 0, 0, false).ts();
+        let end_ts = CTime::new(2024, 1, 3, 12, 0, 0, false).ts();
+        let idx: Vec<usize> = kl_list
+            .klu_range(begin_ts, end_ts)
+            .iter()
+            .map(|klu| klu.idx())
+            .collect();
+        assert_eq!(idx, vec![1, 2]);
+        assert!(kl_list.klu_range(end_ts, begin_ts).is_empty());
+
+        assert_eq!(kl_list.last_n_klu(2)[0].idx(), 2);
+        assert_eq!(kl_list.last_n_klu(10).len(), 4);
+
+        // 第1、2根KLU合并为一根KLC
+        let idx: Vec<usize> = kl_list
+            .query_range(begin_ts, end_ts + 86400)
+            .iter()
+            .map(|klc| klc.idx)
+            .collect();
+        assert_eq!(idx, vec![1, 2]);
+        assert!(kl_list.query_range(end_ts, begin_ts).is_empty());
+    }
+
+    #[test]
+    fn test_trim_before() {
+        let mut kl_list = KLineList::new(Some(KLineType::KDay));
+        feed(
+            &mut kl_list,
+            &[
+                (10.0, 8.0),
+                (11.0, 9.0),
+                (10.5, 9.5),
+                (9.0, 7.0),
+                (8.5, 7.5),
+            ],
+        );
+        // 1月3日落在第1根KLC（1月2日~1月3日）内，整根保留
+        let ts = CTime::new(2024, 1, 3, 0, 0, 0, false).ts();
+        assert_eq!(kl_list.trim_before(ts), 1);
+        assert_eq!(kl_list.len(), 2);
+        assert_eq!(kl_list.klu_cnt(), 4);
+        assert_eq!(kl_list[0].idx, 0);
+        assert_eq!(kl_list[1].klu_range(), 2..=3);
+        assert_eq!(kl_list.klu(3).unwrap().klc(), Some(1));
+        assert_eq!(kl_list.klu(3).unwrap().idx(), 3);
+        assert_eq!(kl_list.trim_before(0), 0);
+    }
+
+    fn feed_times(kl_list: &mut KLineList, times: &[(u32, u32)]) {
+        for (i, &(hour, minute)) in times.iter().enumerate() {
+            let t = CTime::new(2024, 1, 2, hour, minute, 0, false);
+            let price = 10.0 + i as f64;
+            let klu =
+                KLineUnit::new(t, price, price, price, price, TradeInfo::default(), false).unwrap();
+            kl_list.add_single_klu(klu).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_stitch_sub_level() {
+        let mut parent = KLineList::new(Some(KLineType::K30M));
+        let mut sub = KLineList::new(Some(KLineType::K5M));
+        feed_times(&mut parent, &[(10, 0), (10, 30)]);
+        feed_times(&mut sub, &[(9, 35), (9, 40), (10, 0), (10, 5)]);
+        assert_eq!(parent.stitch_sub_level(&mut sub, 2).unwrap(), 4);
+        assert_eq!(parent.klu(0).unwrap().sub_kl_list, vec![0, 1, 2]);
+        assert_eq!(sub.klu(3).unwrap().sup_kl, Some(1));
+
+        // 父级别还没到的子KLU留待下次挂接
+        feed_times(&mut sub, &[(10, 35)]);
+        assert_eq!(parent.stitch_sub_level(&mut sub, 2).unwrap(), 0);
+        assert_eq!(sub.klu(4).unwrap().sup_kl, None);
+
+        let err = sub.stitch_sub_level(&mut parent, 2).unwrap_err();
+        assert_eq!(err.errcode, ErrCode::ParaError);
+
+        let aligned: Vec<(usize, Vec<usize>)> = parent
+            .iter_with_sub(&sub)
+            .map(|(klu, children)| (klu.idx(), children.iter().map(|c| c.idx()).collect()))
+            .collect();
+        assert_eq!(aligned, vec![(0, vec![0, 1, 2]), (1, vec![3])]);
+    }
+
+    #[test]
+    fn test_stitch_sub_level_misalign() {
+        let mut parent = KLineList::new(Some(KLineType::K30M));
+        let mut sub = KLineList::new(Some(KLineType::K5M));
+        feed_times(&mut parent, &[(10, 0), (10, 30), (11, 0), (11, 30)]);
+        feed_times(&mut sub, &[(9, 55), (11, 25)]);
+        let err = parent.stitch_sub_level(&mut sub, 2).unwrap_err();
+        assert_eq!(err.errcode, ErrCode::KlDataNotAlign);
+
+        // 子级别历史比父级别晚开始不算对不齐
+        let mut parent = KLineList::new(Some(KLineType::K30M));
+        let mut sub = KLineList::new(Some(KLineType::K5M));
+        feed_times(&mut parent, &[(10, 0), (10, 30), (11, 0), (11, 30)]);
+        feed_times(&mut sub, &[(11, 5), (11, 25)]);
+        assert_eq!(parent.stitch_sub_level(&mut sub, 2).unwrap(), 2);
+        assert_eq!(parent.klu(3).unwrap().sub_kl_list, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_stitch_sub_level_day() {
+        let mut parent = KLineList::new(Some(KLineType::KDay));
+        let mut sub = KLineList::new(Some(KLineType::K60M));
+        let bar = |t: CTime, price: f64| {
+            KLineUnit::new(t, price, price, price, price, TradeInfo::default(), false).unwrap()
+        };
+        for day in [2, 3] {
+            let t = CTime::new(2024, 1, day, 0, 0, 0, false);
+            parent.add_single_klu(bar(t, day as f64)).unwrap();
+        }
+        for day in [2, 3] {
+            for (hour, minute) in [(10, 30), (11, 30), (14, 0), (15, 0)] {
+                let t = CTime::new(2024, 1, day, hour, minute, 0, false);
+                sub.add_single_klu(bar(t, day as f64)).unwrap();
+            }
+        }
+        assert_eq!(parent.stitch_sub_level(&mut sub, 2).unwrap(), 8);
+        assert_eq!(parent.klu(0).unwrap().sub_kl_list, vec![0, 1, 2, 3]);
+        assert_eq!(parent.klu(1).unwrap().sub_kl_list, vec![4, 5, 6, 7]);
+    }
+
+    fn build(bars: &[(f64, f64)], day_offset: u32) -> KLineList {
+        let mut kl_list = KLineList::new(Some(KLineType::KDay));
+        for (i, &(high, low)) in bars.iter().enumerate() {
+            let t = CTime::new(2024, 1, 1 + day_offset + i as u32, 0, 0, 0, false);
+            let klu = KLineUnit::new(t, low, high, low, high, TradeInfo::default(), false).unwrap();
+            kl_list.add_single_klu(klu).unwrap();
+        }
+        kl_list
+    }
+
+    fn klc_summary(kl_list: &KLineList) -> Vec<(f64, f64, usize, FxType)> {
+        kl_list
+            .iter()
+            .map(|klc| (klc.high(), klc.low(), klc.len(), klc.fx()))
+            .collect()
+    }
+
+    #[test]
+    fn test_merge_tail_and_backfill() {
+        let bars = [
+            (10.0, 8.0),
+            (11.0, 9.0),
+            (10.5, 9.5),
+            (9.0, 7.0),
+            (8.5, 7.5),
+            (9.5, 8.0),
+            (10.0, 8.5),
+        ];
+        let full = build(&bars, 0);
+
+        let mut cached = build(&bars[..3], 0);
+        cached.merge(build(&bars[3..], 3)).unwrap();
+        assert_eq!(cached.klu_cnt(), bars.len());
+        assert_eq!(klc_summary(&cached), klc_summary(&full));
+
+        let mut recent = build(&bars[5..], 5);
+        let mut older = build(&bars[..5], 0);
+        older.klus[0].sup_kl = Some(3);
+        recent.klus[0].sub_kl_list.push(7);
+        recent.merge(older).unwrap();
+        assert_eq!(recent.klu_cnt(), bars.len());
+        assert_eq!(klc_summary(&recent), klc_summary(&full));
+        // 下标都变了，跨级别挂接全部清空
+        assert_eq!(recent.klu(0).unwrap().sup_kl, None);
+        assert!(recent.klu(5).unwrap().sub_kl_list.is_empty());
+
+        // 与已有部分重叠的两段不能合并，原列表不变
+        let mut cached = build(&[(10.0, 8.0), (11.0, 9.0), (10.5, 9.5), (12.0, 7.0)], 0);
+        let err = cached.merge(build(&bars[3..], 3)).unwrap_err();
+        assert_eq!(err.errcode, ErrCode::KlNotMonotonous);
+        assert_eq!(cached.klu_cnt(), 4);
+        let mut inner = full.clone();
+        let err = inner.merge(build(&bars[2..4], 2)).unwrap_err();
+        assert_eq!(err.errcode, ErrCode::KlNotMonotonous);
+        assert_eq!(klc_summary(&inner), klc_summary(&full));
+
+        let mut empty = KLineList::new(Some(KLineType::KDay));
+        empty.merge(build(&bars, 0)).unwrap();
+        assert_eq!(klc_summary(&empty), klc_summary(&full));
+    }
+
+    #[test]
+    fn test_merge_macd() {
+        let macd_of = |kl_list: &KLineList| -> Vec<_> {
+            (0..kl_list.klu_cnt())
+                .map(|i| kl_list.klu(i).unwrap().trade_info.macd)
+                .collect()
+        };
+        let bars = [
+            (10.0, 8.0),
+            (11.0, 9.0),
+            (10.5, 9.5),
+            (9.0, 7.0),
+            (8.5, 7.5),
+            (9.5, 8.0),
+            (10.0, 8.5),
+        ];
+        let full = macd_of(&build(&bars, 0));
+
+        let mut cached = build(&bars[..3], 0);
+        cached.merge(build(&bars[3..], 3)).unwrap();
+        assert_eq!(macd_of(&cached), full);
+
+        let mut recent = build(&bars[5..], 5);
+        recent.merge(build(&bars[..5], 0)).unwrap();
+        assert_eq!(macd_of(&recent), full);
+
+        // 回退之后接着算
+        let mut rolled = build(&[(10.0, 8.0), (11.0, 9.0), (10.5, 9.5), (12.0, 7.0)], 0);
+        rolled.truncate_klus(3).unwrap();
+        rolled.merge(build(&bars[3..], 3)).unwrap();
+        assert_eq!(macd_of(&rolled), full);
+
+        // 合并进空列表时不能沿用之前残留的状态
+        let mut emptied = build(&bars, 0);
+        emptied.trim_before(i64::MAX);
+        emptied.merge(build(&bars, 0)).unwrap();
+        assert_eq!(macd_of(&emptied), full);
+    }
+
+    fn bar(day: u32, close: f64, volume: f64) -> KLineUnit {
+        let t = CTime::new(2024, 1, day, 0, 0, 0, false);
+        KLineUnit::builder(t)
+            .open(close)
+            .high(close * 1.01)
+            .low(close * 0.99)
+            .close(close)
+            .volume(volume)
+            .build()
+            .unwrap()
+    }
+
+    fn policy_list(policy: InvalidBarPolicy) -> KLineList {
+        let config = ChanConfig {
+            on_invalid_bar: Some(policy),
+            max_price_change_rate: 0.2,
+            max_zero_volume_cnt: 2,
+            ..Default::default()
+        };
+        KLineList::with_config(Some(KLineType::KDay), config)
+    }
+
+    #[test]
+    fn test_invalid_bar_policy() {
+        let mut kl_list = policy_list(InvalidBarPolicy::Error);
+        kl_list.add_single_klu(bar(2, 10.0, 100.0)).unwrap();
+        let err = kl_list.add_single_klu(bar(3, 20.0, 100.0)).unwrap_err();
+        assert_eq!(err.errcode, ErrCode::KlDataInvalid);
+        let err = kl_list.add_single_klu(bar(1, 10.0, 100.0)).unwrap_err();
+        assert_eq!(err.errcode, ErrCode::KlNotMonotonous);
+        kl_list.add_single_klu(bar(3, 10.5, 0.0)).unwrap();
+        kl_list.add_single_klu(bar(4, 10.6, 0.0)).unwrap();
+        let err = kl_list.add_single_klu(bar(5, 10.7, 0.0)).unwrap_err();
+        assert_eq!(err.errcode, ErrCode::TradeinfoTooMuchZero);
+
+        let mut kl_list = policy_list(InvalidBarPolicy::Skip);
+        kl_list.add_single_klu(bar(2, 10.0, 100.0)).unwrap();
+        kl_list.add_single_klu(bar(3, 20.0, 100.0)).unwrap();
+        assert_eq!((kl_list.klu_cnt(), kl_list.invalid_bar_cnt()), (1, 1));
+
+        let mut kl_list = policy_list(InvalidBarPolicy::Clamp);
+        kl_list.add_single_klu(bar(2, 10.0, 100.0)).unwrap();
+        kl_list.add_single_klu(bar(3, 20.0, 100.0)).unwrap();
+        kl_list.add_single_klu(bar(3, 11.0, 100.0)).unwrap();
+        assert_eq!((kl_list.klu_cnt(), kl_list.invalid_bar_cnt()), (2, 2));
+        assert_eq!(kl_list.klu(1).unwrap().high, 12.0);
+        assert_eq!(kl_list.klu(1).unwrap().low, 12.0);
+
+        // 负价修正之后时间仍然倒退，整根丢弃
+        let mut kl_list = policy_list(InvalidBarPolicy::Clamp);
+        kl_list.add_single_klu(bar(5, 10.0, 100.0)).unwrap();
+        let mut negative = bar(3, 10.0, 100.0);
+        negative.low = -1.0;
+        kl_list.add_single_klu(negative).unwrap();
+        assert_eq!((kl_list.klu_cnt(), kl_list.invalid_bar_cnt()), (1, 1));
+        assert!(kl_list.check_invariants().is_empty());
+
+        let mut unordered = kl_list.clone();
+        unordered.klus.push(bar(3, 10.0, 100.0));
+        unordered.klus[1].idx = 1;
+        unordered.klus[1].klc = Some(0);
+        unordered.lst[0] = KLine::from_parts(
+            0,
+            unordered.kl_type,
+            Combiner::from_parts(
+                unordered.klus[0].time,
+                unordered.klus[1].time,
+                10.1,
+                9.9,
+                vec![0, 1],
+                KLineDir::Up,
+                FxType::Unknown,
+            ),
+        );
+        assert_eq!(
+            unordered.check_invariants(),
+            vec![InvariantViolation::KluTimeOrder { klu_idx: 1 }]
+        );
+    }
+
+    #[test]
+    fn test_symbol_info_tick() {
+        let symbol = SymbolInfo::new("HK.00700", 0.2, 100, 1, "HKD").unwrap();
+        let t = CTime::new(2024, 1, 2, 0, 0, 0, false);
+        let off_tick = KLineUnit::from_ohlcv(t, 10.0, 10.35, 9.8, 10.14, TradeInfo::default());
+        let off_tick = off_tick.unwrap();
+
+        let mut kl_list = policy_list(InvalidBarPolicy::Error);
+        kl_list.set_symbol_info(symbol.clone());
+        let err = kl_list.add_single_klu(off_tick.clone()).unwrap_err();
+        assert_eq!(err.errcode, ErrCode::PriceNotOnTick);
+
+        let mut kl_list = policy_list(InvalidBarPolicy::Clamp);
+        kl_list.set_symbol_info(symbol);
+        kl_list.add_single_klu(off_tick).unwrap();
+        let klu = kl_list.klu(0).unwrap();
+        assert_eq!((klu.high, klu.close), (10.4, 10.2));
+        assert_eq!(kl_list.invalid_bar_cnt(), 1);
+    }
+
+    #[test]
+    fn test_suspension_bar() {
+        let mut kl_list = policy_list(InvalidBarPolicy::Error);
+        kl_list.add_single_klu(bar(2, 10.0, 100.0)).unwrap();
+        let t = CTime::new(2024, 1, 3, 0, 0, 0, false);
+        let halted = KLineUnit::from_ohlcv(t, 10.0, 10.0, 10.0, 10.0, TradeInfo::default());
+        let mut halted = halted.unwrap();
+        halted.trade_info.volume = Some(0.0);
+        let err = kl_list.add_single_klu(halted.clone()).unwrap_err();
+        assert_eq!(err.errcode, ErrCode::Suspension);
+
+        // 不做校验时照常接收，但标记为停牌
+        let mut kl_list = KLineList::new(Some(KLineType::KDay));
+        kl_list.add_single_klu(bar(2, 10.0, 100.0)).unwrap();
+        kl_list.add_single_klu(halted.clone()).unwrap();
+        halted.time = CTime::new(2024, 1, 4, 0, 0, 0, false);
+        kl_list.add_single_klu(halted).unwrap();
+        kl_list.add_single_klu(bar(5, 10.5, 100.0)).unwrap();
+        let begin = CTime::new(2024, 1, 3, 0, 0, 0, false);
+        let end = CTime::new(2024, 1, 4, 0, 0, 0, false);
+        assert_eq!(kl_list.halted_ranges(), vec![(begin, end)]);
+        assert_eq!(kl_list.summary().halted_klu_cnt, 2);
+        assert!(kl_list.is_klc_halted(&kl_list[0]));
+    }
+
+    #[test]
+    fn test_tick_level() {
+        let config = ChanConfig {
+            on_invalid_bar: Some(InvalidBarPolicy::Error),
+            ..Default::default()
+        };
+        let mut kl_list = KLineList::with_config(Some(KLineType::KTick), config);
+        let t = CTime::new(2024, 1, 2, 9, 30, 0, false);
+        for price in [10.0, 10.1, 10.1, 10.05, 10.2, 10.0, 9.9] {
+            kl_list
+                .add_single_klu(KLineUnit::from_tick(t, price, 1.0).unwrap())
+                .unwrap();
+        }
+        // 逐笔KLC没有宽度，只有同价的成交会并入同一根KLC
+        assert_eq!(kl_list.klu_cnt(), 7);
+        assert_eq!(kl_list.len(), 6);
+        assert_eq!(kl_list[1].len(), 2);
+        let fx_types: Vec<FxType> = kl_list.fx_list().iter().map(|fx| fx.fx_type).collect();
+        assert_eq!(fx_types, vec![FxType::Top, FxType::Bottom, FxType::Top]);
+
+        let earlier = CTime::new(2024, 1, 2, 9, 29, 59, false);
+        let err = kl_list
+            .add_single_klu(KLineUnit::from_tick(earlier, 10.0, 1.0).unwrap())
+            .unwrap_err();
+        assert_eq!(err.errcode, ErrCode::KlNotMonotonous);
+    }
+
+    #[test]
+    fn test_fx_list() {
+        let bars = [
+            (10.0, 8.0),
+            (11.0, 9.0),
+            (9.0, 7.0),
+            (8.0, 6.0),
+            (9.5, 7.5),
+            (10.5, 8.5),
+        ];
+        let kl_list = build(&bars, 0);
+        let fx_list = kl_list.fx_list();
+        assert_eq!(fx_list.len(), 2);
+        assert_eq!((fx_list[0].fx_type, fx_list[0].klc_idx), (FxType::Top, 1));
+        assert_eq!(
+            (fx_list[1].fx_type, fx_list[1].klc_idx),
+            (FxType::Bottom, 3)
+        );
+        assert!(fx_list[0].is_sure);
+        // 右侧的第4根KLC已经走完
+        assert!(fx_list[1].is_sure);
+        assert_eq!(fx_list.next(&fx_list[0]), Some(&fx_list[1]));
+        assert_eq!(fx_list.pre(&fx_list[0]), None);
+        assert_eq!(fx_list.get_by_klc(3).unwrap().idx, 1);
+        assert!(fx_list.get_by_klc(2).is_none());
+        let ts = CTime::new(2024, 1, 4, 0, 0, 0, false).ts();
+        assert_eq!(fx_list.query_range(ts, ts).len(), 1);
+
+        // 尾部替换后最后一个分形回到候选状态
+        let mut kl_list = build(&bars[..5], 0);
+        assert!(!kl_list.fx_list()[1].is_sure);
+        kl_list.merge(build(&bars[5..], 5)).unwrap();
+        assert_eq!(kl_list.fx_list().len(), 2);
+        assert!(kl_list.fx_list()[1].is_sure);
+        // 回退掉后两根再接上新的尾部，倒数的分形跟着重算
+        kl_list.truncate_klus(4).unwrap();
+        kl_list.merge(build(&[(7.0, 5.0)], 4)).unwrap();
+        assert_eq!(kl_list.fx_list().len(), 1);
+
+        let mut kl_list = build(&bars, 0);
+        kl_list.trim_before(CTime::new(2024, 1, 3, 0, 0, 0, false).ts());
+        assert_eq!(kl_list.fx_list().len(), 1);
+        assert_eq!(kl_list.fx_list()[0].klc_idx, 1);
+    }
+
+    #[test]
+    fn test_fx_id_after_trim() {
+        let series = MarketGen::new(CTime::new(2024, 1, 1, 0, 0, 0, false), 10.0)
+            .trend(4, 2.0, 1.0, 4)
+            .build()
+            .unwrap();
+        let mut kl_list = KLineList::new(Some(KLineType::KDay));
+        for klu in series.klus {
+            kl_list.add_single_klu(klu).unwrap();
+        }
+        let ts = kl_list.fx_list()[2].time_end.ts() + 1;
+        kl_list.trim_before(ts);
+        let ids: Vec<u64> = kl_list.fx_list().iter().map(|fx| fx.id).collect();
+        assert!(ids[0] > 0);
+
+        let restored = KLineList::restore(&kl_list.snapshot()).unwrap();
+        let restored_ids: Vec<u64> = restored.fx_list().iter().map(|fx| fx.id).collect();
+        assert_eq!(restored_ids, ids);
+        assert_eq!(restored.fx_list().next_id(), kl_list.fx_list().next_id());
+
+        // 开头的历史已经丢了，重放出来的编号对不上，直接拒绝
+        let err = kl_list.reconstruct_at(i64::MAX).unwrap_err();
+        assert_eq!(err.errcode, ErrCode::CommonError);
+    }
+
+    #[test]
+    fn test_virtual_fx() {
+        let mut kl_list = build(&[(10.0, 8.0)], 0);
+        assert!(kl_list.virtual_fx().is_none());
+
+        kl_list.merge(build(&[(11.0, 9.0)], 1)).unwrap();
+        let fx = kl_list.virtual_fx().unwrap();
+        assert!(fx.is_virtual && !fx.is_sure);
+        assert_eq!((fx.fx_type, fx.klc_idx, fx.high), (FxType::Top, 1, 11.0));
+
+        // 被合并进最后一根KLC后虚分形跟着更新
+        kl_list.merge(build(&[(11.5, 9.0)], 2)).unwrap();
+        assert_eq!(kl_list.virtual_fx().unwrap().high, 11.5);
+
+        kl_list.merge(build(&[(9.0, 7.0)], 3)).unwrap();
+        let fx = kl_list.virtual_fx().unwrap();
+        assert_eq!((fx.fx_type, fx.klc_idx), (FxType::Bottom, 2));
+        assert_eq!(fx.idx, 1);
+        assert_eq!(kl_list.fx_list().len(), 1);
+        assert!(!kl_list.fx_list()[0].is_virtual);
+    }
+
+    #[test]
+    fn test_gap_fx() {
+        // 第1根KLC向上跳空后又向下跳空，形成两侧都有缺口的顶分型
+        let kl_list = build(&[(10.0, 8.0), (13.0, 11.0), (9.0, 7.0), (8.0, 6.0)], 0);
+        let fx = &kl_list.fx_list()[0];
+        assert_eq!(fx.fx_type, FxType::Top);
+        assert!(fx.gap.with_pre && fx.gap.with_next && fx.gap.is_gap_fx());
+
+        let kl_list = build(&[(10.0, 8.0), (11.0, 9.0), (9.0, 7.0)], 0);
+        assert!(!kl_list.fx_list()[0].gap.is_gap_fx());
+        let fx = kl_list.virtual_fx().unwrap();
+        assert_eq!(fx.gap, FxGap::default());
+    }
+
+    #[test]
+    fn test_fx_config() {
+        let bars = [
+            (10.0, 8.0),
+            (12.0, 9.0),
+            (11.0, 7.0),
+            (13.0, 10.0),
+            (12.0, 8.0),
+        ];
+        let fx_klcs = |min_klc_gap: usize| -> Vec<usize> {
+            let mut kl_list = KLineList::with_config(
+                Some(KLineType::KDay),
+                ChanConfig {
+                    fx_conf: FxConfig {
+                        min_klc_gap,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            );
+            feed(&mut kl_list, &bars);
+            kl_list.fx_list().iter().map(|fx| fx.klc_idx).collect()
+        };
+        assert_eq!(fx_klcs(0), vec![1, 2, 3]);
+        assert_eq!(fx_klcs(2), vec![1, 3]);
+
+        // Break 下 (10,8) 之后的 (10,7) 开出向下的KLC，两根高点相等
+        let fx_types = |allow_equal: bool| -> Vec<(usize, FxType)> {
+            let config = ChanConfig::builder()
+                .equal_extreme(EqualExtremePolicy::Break)
+                .fx_allow_equal(allow_equal)
+                .build()
+                .unwrap();
+            let mut kl_list = KLineList::with_config(Some(KLineType::KDay), config);
+            feed(
+                &mut kl_list,
+                &[(9.0, 7.0), (10.0, 8.0), (10.0, 7.0), (9.0, 6.0)],
+            );
+            assert_eq!(kl_list.lst.len(), 4);
+            kl_list
+                .fx_list()
+                .iter()
+                .map(|fx| (fx.klc_idx, fx.fx_type))
+                .collect()
+        };
+        assert!(fx_types(false).is_empty());
+        assert_eq!(fx_types(true), vec![(1, FxType::Top)]);
+
+        // Break 下 (9,8) 之后的 (10,8) 低点相等，不要求严格占优时才成顶
+        let fx_types = |strict: bool| -> Vec<(usize, FxType)> {
+            let config = ChanConfig::builder()
+                .equal_extreme(EqualExtremePolicy::Break)
+                .fx_strict(strict)
+                .build()
+                .unwrap();
+            let mut kl_list = KLineList::with_config(Some(KLineType::KDay), config);
+            feed(
+                &mut kl_list,
+                &[(9.0, 8.0), (10.0, 8.0), (9.0, 7.0), (9.5, 7.5)],
+            );
+            kl_list
+                .fx_list()
+                .iter()
+                .map(|fx| (fx.klc_idx, fx.fx_type))
+                .collect()
+        };
+        assert_eq!(fx_types(true), vec![(2, FxType::Bottom)]);
+        assert_eq!(fx_types(false), vec![(1, FxType::Top), (2, FxType::Bottom)]);
+    }
+
+    #[test]
+    fn test_element_at() {
+        let mut kl_list = KLineList::new(Some(KLineType::KDay));
+        feed(
+            &mut kl_list,
+            &[(10.0, 8.0), (12.0, 9.0), (11.5, 9.5), (11.0, 7.0)],
+        );
+        let ts = |day: u32| CTime::new(2024, 1, day, 0, 0, 0, false).ts();
+
+        let at = kl_list.element_at(ts(3)).unwrap();
+        assert_eq!((at.klu.idx(), at.klc.idx), (2, 1));
+        assert_eq!(at.fx.unwrap().fx_type, FxType::Top);
+        // 落在两根KLU之间时取后一根
+        let at = kl_list.element_at(ts(1) + 1).unwrap();
+        assert_eq!(at.klu.idx(), 1);
+        let at = kl_list.element_at(ts(1)).unwrap();
+        assert!(at.fx.is_none());
+        assert!(kl_list.element_at(ts(5)).is_none());
+    }
+
+    #[test]
+    fn test_check_invariants() {
+        let mut kl_list = KLineList::new(Some(KLineType::KDay));
+        feed(
+            &mut kl_list,
+            &[
+                (10.0, 8.0),
+                (12.0, 9.0),
+                (11.5, 9.5),
+                (11.0, 7.0),
+                (10.0, 6.0),
+            ],
+        );
+        assert!(kl_list.check_invariants().is_empty());
+        assert_eq!(
+            kl_list.trim_before(CTime::new(2024, 1, 2, 0, 0, 0, false).ts()),
+            1
+        );
+        assert!(kl_list.check_invariants().is_empty());
+
+        kl_list.klus[1].klc = Some(5);
+        kl_list.lst[0].idx = 3;
+        assert_eq!(
+            kl_list.check_invariants(),
+            vec![
+                InvariantViolation::KlcIdx {
+                    klc_idx: 0,
+                    found: 3
+                },
+                InvariantViolation::KluKlc {
+                    klu_idx: 1,
+                    klc_idx: 0,
+                    found: Some(5)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut kl_list = KLineList::new(Some(KLineType::KDay));
+        assert_eq!(kl_list.summary().time_range, None);
+        feed(
+            &mut kl_list,
+            &[
+                (10.0, 8.0),
+                (12.0, 9.0),
+                (11.5, 9.5),
+                (11.0, 7.0),
+                (12.5, 8.0),
+            ],
+        );
+        let summary = kl_list.summary();
+        assert_eq!((summary.klu_cnt, summary.klc_cnt), (5, 4));
+        assert_eq!((summary.top_fx_cnt, summary.bottom_fx_cnt), (1, 1));
+        assert_eq!(summary.unsure_fx_cnt, 1);
+        assert_eq!(summary.avg_klc_len, 1.25);
+        assert_eq!(
+            summary.time_range,
+            Some((kl_list.klus[0].time, kl_list.klus[4].time))
+        );
+    }
+
+    #[test]
+    fn test_fx_volume_strength() {
+        let mut kl_list = KLineList::new(Some(KLineType::KDay));
+        for (i, &(high, low, volume)) in
+            [(10.0, 8.0, 100.0), (12.0, 9.0, 400.0), (11.0, 7.0, 100.0)]
+                .iter()
+                .enumerate()
+        {
+            kl_list
+                .add_single_klu(bar_hlv(i as u32, high, low, volume))
+                .unwrap();
+        }
+        let fx = kl_list.fx_list()[0].clone();
+        assert_eq!(kl_list.fx_volume_strength(&fx), Some(2.0));
+
+        let mut kl_list = KLineList::new(Some(KLineType::KDay));
+        feed(&mut kl_list, &[(10.0, 8.0), (12.0, 9.0), (11.0, 7.0)]);
+        let fx = kl_list.fx_list()[0].clone();
+        assert_eq!(kl_list.fx_volume_strength(&fx), None);
+    }
+
+    fn bar_hlv(day: u32, high: f64, low: f64, volume: f64) -> KLineUnit {
+        let t = CTime::new(2024, 1, 1 + day, 0, 0, 0, false);
+        KLineUnit::builder(t)
+            .open(low)
+            .high(high)
+            .low(low)
+            .close(high)
+            .volume(volume)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_speculate() {
+        let mut kl_list = KLineList::new(Some(KLineType::KDay));
+        feed(&mut kl_list, &[(10.0, 8.0), (12.0, 9.0), (11.0, 7.0)]);
+        assert!(!kl_list.fx_list()[0].is_sure);
+
+        let next = |day: u32, high: f64, low: f64| {
+            let t = CTime::new(2024, 1, day, 0, 0, 0, false);
+            KLineUnit::new(t, low, high, low, high, TradeInfo::default(), false).unwrap()
+        };
+        // 继续下跌后顶分型确定，再连续反弹两根，新出现的底分型也确定
+        let confirmed = kl_list
+            .speculate([next(4, 10.0, 6.0), next(5, 11.0, 7.0), next(6, 12.0, 8.0)])
+            .unwrap();
+        let ids: Vec<(u64, FxType)> = confirmed.iter().map(|fx| (fx.id, fx.fx_type)).collect();
+        assert_eq!(ids, vec![(0, FxType::Top), (1, FxType::Bottom)]);
+        // 原列表不受影响
+        assert_eq!(kl_list.klu_cnt(), 3);
+        assert!(!kl_list.fx_list()[0].is_sure);
+
+        // 只复制尾部推演，结果与整个列表追加后一致
+        let mut series = MarketGen::new(CTime::new(2024, 1, 1, 0, 0, 0, false), 10.0)
+            .trend(4, 2.0, 1.0, 4)
+            .build()
+            .unwrap()
+            .klus;
+        let future = series.split_off(series.len() - 5);
+        let mut kl_list = KLineList::new(Some(KLineType::KDay));
+        for klu in series {
+            kl_list.add_single_klu(klu).unwrap();
+        }
+        assert!(kl_list.fx_list().len() > 3);
+        let mut full = kl_list.clone();
+        for klu in future.clone() {
+            full.add_single_klu(klu).unwrap();
+        }
+        let expected: Vec<Fx> = full
+            .fx_list()
+            .iter()
+            .filter(|fx| fx.is_sure)
+            .filter(|fx| {
+                !kl_list
+                    .fx_list()
+                    .get_by_id(fx.id)
+                    .is_some_and(|fx| fx.is_sure)
+            })
+            .cloned()
+            .collect();
+        assert!(!expected.is_empty());
+        assert_eq!(kl_list.speculate(future).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_reconstruct_at() {
+        let mut kl_list = KLineList::new(Some(KLineType::KDay));
+        feed(
+            &mut kl_list,
+            &[
+                (10.0, 8.0),
+                (12.0, 9.0),
+                (11.0, 7.0),
+                (10.0, 6.0),
+                (11.0, 7.0),
+            ],
+        );
+        assert!(kl_list.fx_list()[0].is_sure);
+
+        let ts = CTime::new(2024, 1, 3, 0, 0, 0, false).ts();
+        let past = kl_list.reconstruct_at(ts).unwrap();
+        assert_eq!(past.klu_cnt(), 3);
+        assert_eq!(past.fx_list().len(), 1);
+        assert_eq!(past.fx_list()[0].id, kl_list.fx_list()[0].id);
+        assert!(!past.fx_list()[0].is_sure);
+
+        kl_list.trim_before(CTime::new(2024, 1, 2, 0, 0, 0, false).ts());
+        assert_eq!(kl_list.trimmed_klu_cnt(), 1);
+        let err = kl_list.reconstruct_at(ts).unwrap_err();
+        assert_eq!(err.errcode, ErrCode::CommonError);
+
+        // 回补合并后分形重新编号，快照恢复后也照样拒绝
+        let bars = [
+            (10.0, 8.0),
+            (11.0, 9.0),
+            (10.5, 9.5),
+            (9.0, 7.0),
+            (8.5, 7.5),
+        ];
+        let mut recent = build(&bars[3..], 3);
+        recent.merge(build(&bars[..3], 0)).unwrap();
+        let err = recent.reconstruct_at(i64::MAX).unwrap_err();
+        assert_eq!(err.errcode, ErrCode::CommonError);
+        let restored = KLineList::restore(&recent.snapshot()).unwrap();
+        assert!(restored.reconstruct_at(i64::MAX).is_err());
+        let mut appended = build(&bars[..3], 0);
+        appended.merge(build(&bars[3..], 3)).unwrap();
+        assert!(appended.reconstruct_at(i64::MAX).is_ok());
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let config = ChanConfig {
+            equal_extreme: EqualExtremePolicy::Break,
+            ..Default::default()
+        };
+        let mut kl_list = KLineList::with_config(Some(KLineType::KDay), config);
+        let series = MarketGen::new(CTime::new(2024, 1, 1, 0, 0, 0, false), 10.0)
+            .trend(3, 2.0, 1.0, 4)
+            .v_reversal(3.0, 3)
+            .build()
+            .unwrap();
+        for mut klu in series.klus {
+            klu.trade_info.extra.insert("vwap".to_string(), klu.close);
+            kl_list.add_single_klu(klu).unwrap();
+        }
+
+        let snapshot = kl_list.snapshot();
+        let restored = KLineList::restore(&snapshot).unwrap();
+        assert_eq!(restored.config, kl_list.config);
+        assert_eq!(restored.summary_str(100), kl_list.summary_str(100));
+        assert_eq!(restored.fx_list().len(), kl_list.fx_list().len());
+        assert_eq!(
+            restored.klu(3).unwrap().trade_info,
+            kl_list.klu(3).unwrap().trade_info
+        );
+        assert_eq!(restored.snapshot(), snapshot);
+
+        // 丢弃开头的历史后快照，KLC边界和分形编号要和丢弃后的列表一致，而不是重放剩余KLU的结果
+        let mut trimmed = kl_list.clone();
+        let ts = trimmed.get(3).unwrap().time_end().ts() + 1;
+        assert!(trimmed.trim_before(ts) > 0);
+        let restored = KLineList::restore(&trimmed.snapshot()).unwrap();
+        assert_eq!(restored.summary_str(100), trimmed.summary_str(100));
+        let fx_ids = |kl_list: &KLineList| -> Vec<_> {
+            kl_list
+                .fx_list()
+                .iter()
+                .map(|fx| (fx.id, fx.klc_idx))
+                .collect()
+        };
+        assert_eq!(fx_ids(&restored), fx_ids(&trimmed));
+        assert_eq!(restored.fx_list().next_id(), trimmed.fx_list().next_id());
+        assert_eq!(restored.trimmed_klu_cnt(), trimmed.trimmed_klu_cnt());
+        assert!(restored.check_invariants().is_empty());
+        // 恢复后接着算，MACD 和新分形的编号都接得上
+        let mut live = trimmed.clone();
+        let mut resumed = restored;
+        for klu in MarketGen::new(CTime::new(2024, 6, 1, 0, 0, 0, false), 12.0)
+            .trend(2, 2.0, 1.0, 4)
+            .build()
+            .unwrap()
+            .klus
+        {
+            live.add_single_klu(klu.clone()).unwrap();
+            resumed.add_single_klu(klu).unwrap();
+        }
+        assert_eq!(fx_ids(&resumed), fx_ids(&live));
+        assert_eq!(resumed.snapshot(), live.snapshot());
+
+        let mut bytes = snapshot.clone().into_bytes();
+        bytes[20] ^= 1;
+        let err = KLineList::restore(&ChanSnapshot::from_bytes(bytes)).unwrap_err();
+        assert_eq!(err.errcode, ErrCode::SnapshotErr);
+        let mut bytes = snapshot.into_bytes();
+        bytes[8] = 9;
+        let err = KLineList::restore(&ChanSnapshot::from_bytes(bytes)).unwrap_err();
+        assert_eq!(err.errcode, ErrCode::SnapshotErr);
+        let err = KLineList::restore(&ChanSnapshot::from_bytes(b"CHAN".to_vec())).unwrap_err();
+        assert_eq!(err.errcode, ErrCode::SnapshotErr);
+
+        // 校验和对得上但KLC范围越界，不能按范围去分配内存
+        let klu = kl_list.klu(0).unwrap();
+        let combiner = Combiner::from_parts(
+            klu.time,
+            klu.time,
+            klu.high,
+            klu.low,
+            vec![0, 1 << 40],
+            KLineDir::Up,
+            FxType::Unknown,
+        );
+        let bad = snapshot::encode(
+            kl_list.kl_type,
+            &kl_list.config,
+            ListState::default(),
+            &kl_list.klus[..1],
+            &[KLine::from_parts(0, kl_list.kl_type, combiner)],
+            &FxList::new(),
+        );
+        let err = KLineList::restore(&bad).unwrap_err();
+        assert_eq!(err.errcode, ErrCode::SnapshotErr);
+    }
+
+    #[test]
+    fn test_macd_filled() {
+        let config = ChanConfig::builder().macd(3, 6, 2).build().unwrap();
+        let mut kl_list = KLineList::with_config(Some(KLineType::KDay), config);
+        feed(
+            &mut kl_list,
+            &[(10.0, 8.0), (12.0, 9.0), (11.0, 7.0), (13.0, 10.0)],
+        );
+        let mut calc = MacdCalculator::new(3, 6, 2).unwrap();
+        for i in 0..kl_list.klu_cnt() {
+            let klu = kl_list.klu(i).unwrap();
+            assert_eq!(klu.trade_info.macd, Some(calc.add(klu.close)));
+        }
+
+        let config = ChanConfig {
+            macd: MacdConfig {
+                fast: 0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut kl_list = KLineList::with_config(Some(KLineType::KDay), config);
+        feed(&mut kl_list, &[(10.0, 8.0)]);
+        assert_eq!(kl_list.klu(0).unwrap().trade_info.macd, None);
+    }
+
+    #[test]
+    fn test_add_klu_slice() {
+        let input = |day: u32, high: f64, low: f64| KluInput {
+            time: CTime::new(2024, 1, day, 0, 0, 0, false),
+            open: low,
+            high,
+            low,
+            close: high,
+            volume: Some(100.0),
+        };
+        let mut kl_list = KLineList::new(Some(KLineType::KDay));
+        kl_list
+            .add_klu_slice(&[
+                input(1, 10.0, 8.0),
+                input(2, 12.0, 9.0),
+                input(3, 11.0, 7.0),
+            ])
+            .unwrap();
+        assert_eq!(
+            (kl_list.klu_cnt(), kl_list.len(), kl_list.fx_list().len()),
+            (3, 3, 1)
+        );
+
+        let (cnt, err) = kl_list
+            .add_klu_slice(&[input(4, 10.0, 6.0), input(5, 6.0, 10.0)])
+            .unwrap_err();
+        assert_eq!((cnt, err.errcode), (1, ErrCode::KlDataInvalid));
+        assert_eq!(kl_list.klu_cnt(), 4);
+    }
+
+    #[test]
+    fn test_summary_str() {
+        let mut kl_list = KLineList::new(Some(KLineType::KDay));
+        feed(
+            &mut kl_list,
+            &[
+                (10.0, 8.0),
+                (12.0, 9.0),
+                (11.0, 7.0),
+                (13.0, 10.0),
+                (12.0, 8.0),
+            ],
+        );
+        let full = kl_list.to_string();
+        assert_eq!(full.lines().count(), 5);
+        assert_eq!(full.lines().next().unwrap(), kl_list[0].to_string());
+
+        let short = kl_list.summary_str(1);
+        let lines: Vec<&str> = short.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1], "... 3 more ...");
+        assert_eq!(lines[2], kl_list[4].to_string());
+        assert_eq!(kl_list.summary_str(3), full);
+    }
+
+    #[test]
+    fn test_profiling() {
+        let mut kl_list = KLineList::new(Some(KLineType::KDay));
+        assert!(kl_list.profiler().is_none());
+        kl_list.enable_profiling();
+        feed(&mut kl_list, &[(10.0, 8.0), (12.0, 9.0), (11.0, 7.0)]);
+        let profiler = kl_list.profiler().unwrap();
+        let phases: Vec<(usize, Phase)> = profiler
+            .records()
+            .iter()
+            .map(|r| (r.klu_idx, r.phase))
+            .collect();
+        // 第三根KLC开出后才开始算分形
+        assert_eq!(
+            phases,
+            vec![
+                (0, Phase::KlcMerge),
+                (1, Phase::KlcMerge),
+                (2, Phase::KlcMerge),
+                (2, Phase::FxDetect)
+            ]
+        );
+        assert!(profiler
+            .to_csv()
+            .starts_with("klu_idx,phase,elapsed_ns\n0,klc_merge,"));
+    }
+
+    #[test]
+    fn test_alerts() {
+        let mut kl_list = KLineList::new(Some(KLineType::KDay));
+        let above = kl_list.add_alert(AlertRule::PriceAbove(11.5));
+        let top = kl_list.add_alert(AlertRule::BreakLastTopFx);
+        let below = kl_list.add_alert(AlertRule::PriceBelow(5.0));
+        kl_list.remove_alert(below);
+        // feed 的收盘价取最高价
+        feed(
+            &mut kl_list,
+            &[(10.0, 8.0), (12.0, 9.0), (11.0, 7.0), (10.0, 6.0)],
+        );
+        let events = kl_list.drain_alerts();
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].rule_id, events[0].klu_idx), (above, 1));
+        assert!(kl_list.drain_alerts().is_empty());
+
+        // 收盘价上穿顶分型高点12
+        let t = CTime::new(2024, 1, 5, 0, 0, 0, false);
+        let klu = KLineUnit::new(t, 10.0, 12.5, 10.0, 12.5, TradeInfo::default(), false).unwrap();
+        kl_list.add_single_klu(klu).unwrap();
+        let rules: Vec<usize> = kl_list.drain_alerts().iter().map(|e| e.rule_id).collect();
+        assert_eq!(rules, vec![above, top]);
+    }
+}
@@ -0,0 +1,325 @@
+use std::fmt;
+
+use crate::common::chan_exception::{ChanException, ChanResult, ErrCode};
+use crate::common::ctime::CTime;
+use crate::common::enums::KLineType;
+use crate::common::messages::{tr, MsgKey};
+use crate::kline::trade_info::TradeInfo;
+
+/// 单根K线
+#[derive(Debug, Clone)]
+pub struct KLineUnit {
+    pub kl_type: Option<KLineType>,
+    pub time: CTime,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub trade_info: TradeInfo,
+    pub sub_kl_list: Vec<usize>, // 次级别KLU的idx
+    pub sup_kl: Option<usize>,   // 指向更高级别KLU的idx
+    pub limit_flag: i32,         // 0:普通 -1:跌停，1:涨停
+    pub is_halted: bool,         // 停牌K线：零成交、一字且收盘价不变
+    pub(crate) klc: Option<usize>,
+    pub(crate) idx: usize,
+}
+
+impl KLineUnit {
+    pub fn new(
+        time: CTime,
+        open: f64,
+        high: f64,
+        low: f64,
+        close: f64,
+        trade_info: TradeInfo,
+        autofix: bool,
+    ) -> ChanResult<Self> {
+        let mut klu = Self {
+            kl_type: None,
+            time,
+            open,
+            high,
+            low,
+            close,
+            trade_info,
+            sub_kl_list: Vec::new(),
+            sup_kl: None,
+            limit_flag: 0,
+            is_halted: false,
+            klc: None,
+            idx: 0,
+        };
+        klu.check(autofix)?;
+        Ok(klu)
+    }
+
+    /// 按原始OHLC构造，价格不合法时直接报错而不是自动修正
+    pub fn from_ohlcv(
+        time: CTime,
+        open: f64,
+        high: f64,
+        low: f64,
+        close: f64,
+        trade_info: TradeInfo,
+    ) -> ChanResult<Self> {
+        Self::new(time, open, high, low, close, trade_info, false)
+    }
+
+    /// 一笔成交对应的KLU，OHLC都等于成交价
+    pub fn from_tick(time: CTime, price: f64, volume: f64) -> ChanResult<Self> {
+        let trade_info = TradeInfo {
+            volume: Some(volume),
+            ..TradeInfo::default()
+        };
+        let mut klu = Self::new(time, price, price, price, price, trade_info, false)?;
+        klu.kl_type = Some(KLineType::KTick);
+        Ok(klu)
+    }
+
+    pub fn builder(time: CTime) -> KLineUnitBuilder {
+        KLineUnitBuilder::new(time)
+    }
+
+    pub fn idx(&self) -> usize {
+        self.idx
+    }
+
+    /// 所属合并K线的idx，尚未加入 `KLineList` 时为 None
+    pub fn klc(&self) -> Option<usize> {
+        self.klc
+    }
+
+    pub fn check(&mut self, autofix: bool) -> ChanResult<()> {
+        let min_price = self.open.min(self.high).min(self.close).min(self.low);
+        let max_price = self.open.max(self.high).max(self.close).max(self.low);
+        if self.low > min_price {
+            if autofix {
+                self.low = min_price;
+            } else {
+                return Err(ChanException::new(
+                    tr(
+                        MsgKey::LowNotMin,
+                        &[
+                            &self.time,
+                            &self.low,
+                            &self.low,
+                            &self.open,
+                            &self.high,
+                            &self.close,
+                        ],
+                    ),
+                    ErrCode::KlDataInvalid,
+                ));
+            }
+        }
+        if self.high < max_price {
+            if autofix {
+                self.high = max_price;
+            } else {
+                return Err(ChanException::new(
+                    tr(
+                        MsgKey::HighNotMax,
+                        &[
+                            &self.time,
+                            &self.high,
+                            &self.low,
+                            &self.open,
+                            &self.high,
+                            &self.close,
+                        ],
+                    ),
+                    ErrCode::KlDataInvalid,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn add_children(&mut self, child: usize) {
+        self.sub_kl_list.push(child);
+    }
+
+    pub fn set_parent(&mut self, parent: usize) {
+        self.sup_kl = Some(parent);
+    }
+}
+
+/// 批量导入用的一根原始K线，只有时间、价格和成交量
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KluInput {
+    pub time: CTime,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: Option<f64>,
+}
+
+impl KluInput {
+    pub fn to_klu(&self, autofix: bool) -> ChanResult<KLineUnit> {
+        let trade_info = TradeInfo {
+            volume: self.volume,
+            ..Default::default()
+        };
+        KLineUnit::new(
+            self.time, self.open, self.high, self.low, self.close, trade_info, autofix,
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct KLineUnitBuilder {
+    time: CTime,
+    open: Option<f64>,
+    high: Option<f64>,
+    low: Option<f64>,
+    close: Option<f64>,
+    trade_info: TradeInfo,
+    kl_type: Option<KLineType>,
+    autofix: bool,
+}
+
+impl KLineUnitBuilder {
+    pub fn new(time: CTime) -> Self {
+        Self {
+            time,
+            open: None,
+            high: None,
+            low: None,
+            close: None,
+            trade_info: TradeInfo::default(),
+            kl_type: None,
+            autofix: false,
+        }
+    }
+
+    pub fn open(mut self, open: f64) -> Self {
+        self.open = Some(open);
+        self
+    }
+
+    pub fn high(mut self, high: f64) -> Self {
+        self.high = Some(high);
+        self
+    }
+
+    pub fn low(mut self, low: f64) -> Self {
+        self.low = Some(low);
+        self
+    }
+
+    pub fn close(mut self, close: f64) -> Self {
+        self.close = Some(close);
+        self
+    }
+
+    pub fn volume(mut self, volume: f64) -> Self {
+        self.trade_info.volume = Some(volume);
+        self
+    }
+
+    pub fn turnover(mut self, turnover: f64) -> Self {
+        self.trade_info.turnover = Some(turnover);
+        self
+    }
+
+    pub fn turnrate(mut self, turnrate: f64) -> Self {
+        self.trade_info.turnrate = Some(turnrate);
+        self
+    }
+
+    pub fn trade_info(mut self, trade_info: TradeInfo) -> Self {
+        self.trade_info = trade_info;
+        self
+    }
+
+    pub fn kl_type(mut self, kl_type: KLineType) -> Self {
+        self.kl_type = Some(kl_type);
+        self
+    }
+
+    pub fn autofix(mut self, autofix: bool) -> Self {
+        self.autofix = autofix;
+        self
+    }
+
+    pub fn build(self) -> ChanResult<KLineUnit> {
+        let missing = |name: &str| {
+            ChanException::new(
+                tr(MsgKey::PriceMissing, &[&self.time, &name]),
+                ErrCode::ParaError,
+            )
+        };
+        let open = self.open.ok_or_else(|| missing("open"))?;
+        let high = self.high.ok_or_else(|| missing("high"))?;
+        let low = self.low.ok_or_else(|| missing("low"))?;
+        let close = self.close.ok_or_else(|| missing("close"))?;
+        let mut klu = KLineUnit::new(
+            self.time,
+            open,
+            high,
+            low,
+            close,
+            self.trade_info,
+            self.autofix,
+        )?;
+        klu.kl_type = self.kl_type;
+        Ok(klu)
+    }
+}
+
+impl fmt::Display for KLineUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}/{:?} open={} close={} high={} low={} {}",
+            self.idx,
+            self.time,
+            self.kl_type,
+            self.open,
+            self.close,
+            self.high,
+            self.low,
+            self.trade_info
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_autofix() {
+        let t = CTime::new(2024, 1, 2, 0, 0, 0, false);
+        let err = KLineUnit::new(t, 10.0, 9.0, 8.0, 9.5, TradeInfo::default(), false).unwrap_err();
+        assert_eq!(err.errcode, ErrCode::KlDataInvalid);
+
+        let klu = KLineUnit::new(t, 10.0, 9.0, 8.0, 9.5, TradeInfo::default(), true).unwrap();
+        assert_eq!(klu.high, 10.0);
+        assert_eq!(klu.low, 8.0);
+        // 还没加入列表
+        assert_eq!(klu.klc(), None);
+    }
+
+    #[test]
+    fn test_builder() {
+        let t = CTime::new(2024, 1, 2, 0, 0, 0, false);
+        let klu = KLineUnit::builder(t)
+            .open(10.0)
+            .high(11.0)
+            .low(9.0)
+            .close(10.5)
+            .volume(1000.0)
+            .kl_type(KLineType::KDay)
+            .build()
+            .unwrap();
+        assert_eq!(klu.trade_info.volume, Some(1000.0));
+        assert_eq!(klu.kl_type, Some(KLineType::KDay));
+
+        let err = KLineUnit::builder(t).open(10.0).build().unwrap_err();
+        assert_eq!(err.errcode, ErrCode::ParaError);
+        let err = KLineUnit::from_ohlcv(t, 12.0, 11.0, 9.0, 10.5, TradeInfo::default());
+        assert_eq!(err.unwrap_err().errcode, ErrCode::KlDataInvalid);
+    }
+}
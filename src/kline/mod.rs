@@ -0,0 +1,12 @@
+pub mod alert;
+pub mod bar_builder;
+pub mod config_diff;
+pub mod fx_list;
+#[allow(clippy::module_inception)]
+pub mod kline;
+pub mod kline_list;
+pub mod kline_unit;
+pub mod robustness;
+pub mod snapshot;
+pub mod trade_info;
+pub mod warm_up;
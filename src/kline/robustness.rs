@@ -0,0 +1,210 @@
+use crate::chan_config::ChanConfig;
+use crate::common::chan_exception::ChanResult;
+use crate::common::enums::KLineType;
+use crate::kline::config_diff::{fx_keys, run, FxKey};
+use crate::kline::kline_unit::KLineUnit;
+
+/// 对历史K线的扰动方式，每根KLU的OHLC整体乘同一个正系数，保证扰动后仍是合法K线
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Perturbation {
+    /// 系数为 1 + u * scale，u 在 [-1, 1] 上均匀分布
+    Noise(f64),
+    /// 系数为 1 + r * scale，r 从原始序列的收盘涨跌幅中有放回抽样
+    Bootstrap(f64),
+}
+
+/// 多次扰动后，原始序列上每个分形的存活情况
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RobustnessReport {
+    pub runs: usize,
+    /// 原始序列的分形及其在扰动后仍出现（类型和顶点时间都相同）的比例
+    pub fx_survival: Vec<(FxKey, f64)>,
+    /// 扰动后平均分形数
+    pub avg_fx_cnt: f64,
+}
+
+impl RobustnessReport {
+    /// 存活比例不低于 `threshold` 的分形占比
+    pub fn stable_ratio(&self, threshold: f64) -> f64 {
+        if self.fx_survival.is_empty() {
+            return 0.0;
+        }
+        let cnt = self
+            .fx_survival
+            .iter()
+            .filter(|(_, ratio)| *ratio >= threshold)
+            .count();
+        cnt as f64 / self.fx_survival.len() as f64
+    }
+}
+
+/// xorshift64*，只用于生成扰动，`seed` 相同则结果可复现
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed.max(1))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// [0, 1) 上均匀分布
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// 把 `klus` 按 `perturbation` 扰动 `runs` 次，每次重新计算分形，统计原始分形的稳定程度
+pub fn evaluate_robustness(
+    klus: &[KLineUnit],
+    kl_type: Option<KLineType>,
+    conf: &ChanConfig,
+    perturbation: Perturbation,
+    runs: usize,
+    seed: u64,
+) -> ChanResult<RobustnessReport> {
+    let base = run(klus, kl_type, conf.clone())?;
+    let base_keys: Vec<FxKey> = fx_keys(&base)?.into_iter().map(|(key, _)| key).collect();
+    let rets: Vec<f64> = klus
+        .windows(2)
+        .map(|w| w[1].close / w[0].close - 1.0)
+        .collect();
+
+    let mut rng = Rng::new(seed);
+    let mut hit_cnt = vec![0usize; base_keys.len()];
+    let mut fx_cnt = 0;
+    for _ in 0..runs {
+        let perturbed: Vec<KLineUnit> = klus
+            .iter()
+            .map(|klu| {
+                let r = match perturbation {
+                    Perturbation::Noise(scale) => (rng.next_f64() * 2.0 - 1.0) * scale,
+                    Perturbation::Bootstrap(scale) if !rets.is_empty() => {
+                        rets[(rng.next_u64() % rets.len() as u64) as usize] * scale
+                    }
+                    Perturbation::Bootstrap(_) => 0.0,
+                };
+                let factor = (1.0 + r).max(f64::EPSILON);
+                let mut klu = klu.clone();
+                klu.open *= factor;
+                klu.high *= factor;
+                klu.low *= factor;
+                klu.close *= factor;
+                klu
+            })
+            .collect();
+        let kl_list = run(&perturbed, kl_type, conf.clone())?;
+        let keys = fx_keys(&kl_list)?;
+        fx_cnt += keys.len();
+        // 两边顶点时间都严格递增，归并一遍即可
+        let mut iter = keys.iter().peekable();
+        for (idx, base_key) in base_keys.iter().enumerate() {
+            while iter
+                .next_if(|(key, _)| key.peak_time < base_key.peak_time)
+                .is_some()
+            {}
+            if iter.next_if(|(key, _)| key == base_key).is_some() {
+                hit_cnt[idx] += 1;
+            }
+        }
+    }
+
+    let denom = runs.max(1) as f64;
+    Ok(RobustnessReport {
+        runs,
+        fx_survival: base_keys
+            .into_iter()
+            .zip(hit_cnt)
+            .map(|(key, hit)| (key, hit as f64 / denom))
+            .collect(),
+        avg_fx_cnt: fx_cnt as f64 / denom,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::ctime::CTime;
+    use crate::common::enums::FxType;
+    use crate::kline::trade_info::TradeInfo;
+
+    fn klus(bars: &[(f64, f64)]) -> Vec<KLineUnit> {
+        bars.iter()
+            .enumerate()
+            .map(|(i, &(high, low))| {
+                let t = CTime::new(2024, 1, 1 + i as u32, 0, 0, 0, false);
+                KLineUnit::new(t, low, high, low, high, TradeInfo::default(), false).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_evaluate_robustness() {
+        // 12 的顶分形很明显，10.05/10.0 附近的底分形只差一点点
+        let klus = klus(&[
+            (10.0, 9.0),
+            (12.0, 11.0),
+            (10.1, 10.0),
+            (10.2, 10.05),
+            (11.0, 10.5),
+        ]);
+        let conf = ChanConfig::default();
+        let report = evaluate_robustness(
+            &klus,
+            Some(KLineType::KDay),
+            &conf,
+            Perturbation::Noise(0.0),
+            3,
+            7,
+        )
+        .unwrap();
+        assert_eq!(report.fx_survival.len(), 2);
+        assert_eq!(report.stable_ratio(1.0), 1.0);
+
+        let report = evaluate_robustness(
+            &klus,
+            Some(KLineType::KDay),
+            &conf,
+            Perturbation::Noise(0.02),
+            50,
+            7,
+        )
+        .unwrap();
+        let survival = |fx_type| {
+            report
+                .fx_survival
+                .iter()
+                .find(|(key, _)| key.fx_type == fx_type)
+                .unwrap()
+                .1
+        };
+        assert_eq!(survival(FxType::Top), 1.0);
+        assert!(survival(FxType::Bottom) < 1.0);
+
+        // 相同种子结果可复现
+        let again = evaluate_robustness(
+            &klus,
+            Some(KLineType::KDay),
+            &conf,
+            Perturbation::Bootstrap(0.5),
+            10,
+            3,
+        )
+        .unwrap();
+        let again2 = evaluate_robustness(
+            &klus,
+            Some(KLineType::KDay),
+            &conf,
+            Perturbation::Bootstrap(0.5),
+            10,
+            3,
+        )
+        .unwrap();
+        assert_eq!(again, again2);
+    }
+}
@@ -0,0 +1,598 @@
+//! `KLineList` 的二进制快照，供崩溃恢复和暂停续算使用
+//!
+//! 格式：魔数 + 版本号 + 级别 + 配置 + 列表状态 + 全部KLU + 全部KLC + 全部分形，
+//! 末尾是前面所有字节的 FNV-1a 校验和，数值一律小端。KLC和分形原样写入而不是恢复时重放KLU：
+//! `trim_before` 之后开头的历史已经没有了，重放得到的KLC边界、方向和分形编号都可能不同
+
+use crate::bi::bi_config::BiConfig;
+use crate::chan_config::{
+    ChanConfig, CombineDirPolicy, EqualExtremePolicy, FxConfig, InvalidBarPolicy, MacdConfig,
+};
+use crate::combiner::kline_combiner::Combiner;
+use crate::common::chan_exception::{ChanException, ChanResult, ErrCode};
+use crate::common::ctime::CTime;
+use crate::common::enums::{FxCheckMethod, FxType, KLineDir, KLineType};
+use crate::common::messages::{tr, MsgKey};
+use crate::kline::fx_list::{Fx, FxGap, FxList};
+use crate::kline::kline::KLine;
+use crate::kline::kline_unit::KLineUnit;
+use crate::kline::trade_info::TradeInfo;
+use crate::math::macd::MacdItem;
+
+const MAGIC: &[u8; 8] = b"CHANSNAP";
+pub const SNAPSHOT_VERSION: u16 = 6;
+
+/// `KLineList::snapshot` 的结果，可以直接落盘，读回后交给 `KLineList::restore`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChanSnapshot {
+    bytes: Vec<u8>,
+}
+
+impl ChanSnapshot {
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self { bytes }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// 除KLU、KLC和分形之外需要原样保存的列表状态
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct ListState {
+    pub invalid_bar_cnt: usize,
+    pub trimmed_klu_cnt: usize,
+    pub rebuilt: bool,
+}
+
+/// 从快照中解出的内容，由 `KLineList::restore` 装回
+pub(crate) struct SnapshotData {
+    pub kl_type: Option<KLineType>,
+    pub config: ChanConfig,
+    pub state: ListState,
+    pub klus: Vec<KLineUnit>,
+    pub lst: Vec<KLine>,
+    pub fx_list: FxList,
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+pub(crate) fn corrupted(reason: &str) -> ChanException {
+    ChanException::new(
+        tr(MsgKey::SnapshotCorrupted, &[&reason]),
+        ErrCode::SnapshotErr,
+    )
+}
+
+#[derive(Default)]
+struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, v: u8) {
+        self.buf.push(v);
+    }
+
+    fn bool(&mut self, v: bool) {
+        self.u8(v as u8);
+    }
+
+    fn u16(&mut self, v: u16) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn i32(&mut self, v: i32) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn u64(&mut self, v: u64) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn f64(&mut self, v: f64) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn opt_f64(&mut self, v: Option<f64>) {
+        self.bool(v.is_some());
+        self.f64(v.unwrap_or_default());
+    }
+
+    fn str(&mut self, v: &str) {
+        self.u32(v.len() as u32);
+        self.buf.extend_from_slice(v.as_bytes());
+    }
+
+    fn kl_type(&mut self, v: Option<KLineType>) {
+        self.i32(v.map_or(-1, |kl_type| kl_type as i32));
+    }
+
+    fn time(&mut self, t: &CTime) {
+        self.i32(t.year);
+        for v in [t.month, t.day, t.hour, t.minute, t.second] {
+            self.u8(v as u8);
+        }
+        self.bool(t.auto);
+    }
+
+    fn dir(&mut self, v: KLineDir) {
+        self.u8(match v {
+            KLineDir::Up => 0,
+            KLineDir::Down => 1,
+            KLineDir::Combine => 2,
+            KLineDir::Included => 3,
+        });
+    }
+
+    fn fx_type(&mut self, v: FxType) {
+        self.u8(match v {
+            FxType::Bottom => 0,
+            FxType::Top => 1,
+            FxType::Unknown => 2,
+        });
+    }
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> ChanResult<&'a [u8]> {
+        let end = self.pos.checked_add(n).filter(|&end| end <= self.buf.len());
+        let end = end.ok_or_else(|| corrupted("truncated"))?;
+        let bytes = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> ChanResult<[u8; N]> {
+        Ok(self.take(N)?.try_into().expect("length checked by take"))
+    }
+
+    fn u8(&mut self) -> ChanResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn bool(&mut self) -> ChanResult<bool> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(corrupted("bad bool")),
+        }
+    }
+
+    fn u16(&mut self) -> ChanResult<u16> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> ChanResult<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn i32(&mut self) -> ChanResult<i32> {
+        Ok(i32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> ChanResult<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn usize(&mut self) -> ChanResult<usize> {
+        usize::try_from(self.u64()?).map_err(|_| corrupted("length overflow"))
+    }
+
+    fn f64(&mut self) -> ChanResult<f64> {
+        Ok(f64::from_le_bytes(self.array()?))
+    }
+
+    fn opt_f64(&mut self) -> ChanResult<Option<f64>> {
+        let some = self.bool()?;
+        let v = self.f64()?;
+        Ok(some.then_some(v))
+    }
+
+    fn str(&mut self) -> ChanResult<String> {
+        let len = self.u32()? as usize;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| corrupted("bad utf-8"))
+    }
+
+    fn kl_type(&mut self) -> ChanResult<Option<KLineType>> {
+        match self.i32()? {
+            -1 => Ok(None),
+            v => KLineType::from_value(v)
+                .map(Some)
+                .ok_or_else(|| corrupted("bad kl_type")),
+        }
+    }
+
+    fn time(&mut self) -> ChanResult<CTime> {
+        let year = self.i32()?;
+        let mut fields = [0u32; 5];
+        for v in &mut fields {
+            *v = self.u8()? as u32;
+        }
+        let [month, day, hour, minute, second] = fields;
+        Ok(CTime::new(
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            self.bool()?,
+        ))
+    }
+
+    fn dir(&mut self) -> ChanResult<KLineDir> {
+        Ok(match self.u8()? {
+            0 => KLineDir::Up,
+            1 => KLineDir::Down,
+            2 => KLineDir::Combine,
+            3 => KLineDir::Included,
+            _ => return Err(corrupted("bad dir")),
+        })
+    }
+
+    fn fx_type(&mut self) -> ChanResult<FxType> {
+        Ok(match self.u8()? {
+            0 => FxType::Bottom,
+            1 => FxType::Top,
+            2 => FxType::Unknown,
+            _ => return Err(corrupted("bad fx_type")),
+        })
+    }
+}
+
+fn write_config(w: &mut Writer, config: &ChanConfig) {
+    let bi = &config.bi_conf;
+    w.str(&bi.bi_algo);
+    w.bool(bi.is_strict);
+    w.u8(match bi.bi_fx_check {
+        FxCheckMethod::Strict => 0,
+        FxCheckMethod::Loss => 1,
+        FxCheckMethod::Half => 2,
+        FxCheckMethod::Totally => 3,
+    });
+    w.bool(bi.gap_as_kl);
+    w.bool(bi.bi_end_is_peak);
+    w.bool(bi.bi_allow_sub_peak);
+    w.u64(config.fx_conf.min_klc_gap as u64);
+    w.bool(config.fx_conf.strict);
+    w.bool(config.fx_conf.allow_equal);
+    w.u8(match config.combine_dir {
+        CombineDirPolicy::Trend => 0,
+        CombineDirPolicy::Close => 1,
+    });
+    w.u8(match config.equal_extreme {
+        EqualExtremePolicy::Merge => 0,
+        EqualExtremePolicy::Break => 1,
+    });
+    for v in [config.macd.fast, config.macd.slow, config.macd.signal] {
+        w.u64(v as u64);
+    }
+    w.u8(match config.on_invalid_bar {
+        None => 0,
+        Some(InvalidBarPolicy::Skip) => 1,
+        Some(InvalidBarPolicy::Error) => 2,
+        Some(InvalidBarPolicy::Clamp) => 3,
+    });
+    w.f64(config.max_price_change_rate);
+    w.u64(config.max_zero_volume_cnt as u64);
+}
+
+fn read_config(r: &mut Reader) -> ChanResult<ChanConfig> {
+    let bi_conf = BiConfig {
+        bi_algo: r.str()?,
+        is_strict: r.bool()?,
+        bi_fx_check: match r.u8()? {
+            0 => FxCheckMethod::Strict,
+            1 => FxCheckMethod::Loss,
+            2 => FxCheckMethod::Half,
+            3 => FxCheckMethod::Totally,
+            _ => return Err(corrupted("bad bi_fx_check")),
+        },
+        gap_as_kl: r.bool()?,
+        bi_end_is_peak: r.bool()?,
+        bi_allow_sub_peak: r.bool()?,
+    };
+    let fx_conf = FxConfig {
+        min_klc_gap: r.usize()?,
+        strict: r.bool()?,
+        allow_equal: r.bool()?,
+    };
+    let combine_dir = match r.u8()? {
+        0 => CombineDirPolicy::Trend,
+        1 => CombineDirPolicy::Close,
+        _ => return Err(corrupted("bad combine_dir")),
+    };
+    let equal_extreme = match r.u8()? {
+        0 => EqualExtremePolicy::Merge,
+        1 => EqualExtremePolicy::Break,
+        _ => return Err(corrupted("bad equal_extreme")),
+    };
+    let macd = MacdConfig {
+        fast: r.usize()?,
+        slow: r.usize()?,
+        signal: r.usize()?,
+    };
+    let on_invalid_bar = match r.u8()? {
+        0 => None,
+        1 => Some(InvalidBarPolicy::Skip),
+        2 => Some(InvalidBarPolicy::Error),
+        3 => Some(InvalidBarPolicy::Clamp),
+        _ => return Err(corrupted("bad on_invalid_bar")),
+    };
+    Ok(ChanConfig {
+        bi_conf,
+        fx_conf,
+        combine_dir,
+        equal_extreme,
+        macd,
+        on_invalid_bar,
+        max_price_change_rate: r.f64()?,
+        max_zero_volume_cnt: r.u64()?.try_into().unwrap_or(usize::MAX),
+    })
+}
+
+fn write_klu(w: &mut Writer, klu: &KLineUnit) {
+    w.time(&klu.time);
+    w.kl_type(klu.kl_type);
+    for v in [klu.open, klu.high, klu.low, klu.close] {
+        w.f64(v);
+    }
+    let info = &klu.trade_info;
+    for v in [
+        info.volume,
+        info.turnover,
+        info.turnrate,
+        info.open_interest,
+        info.bid,
+        info.ask,
+        info.trade_count,
+    ] {
+        w.opt_f64(v);
+    }
+    // 按名字排序，保证同样的状态得到同样的快照
+    let mut extra: Vec<_> = info.extra.iter().collect();
+    extra.sort_by(|a, b| a.0.cmp(b.0));
+    w.u32(extra.len() as u32);
+    for (name, &value) in extra {
+        w.str(name);
+        w.f64(value);
+    }
+    w.i32(klu.limit_flag);
+    w.bool(klu.is_halted);
+    let macd = info.macd.unwrap_or_default();
+    w.bool(info.macd.is_some());
+    for v in [macd.fast_ema, macd.slow_ema, macd.dif, macd.dea, macd.macd] {
+        w.f64(v);
+    }
+}
+
+fn read_klu(r: &mut Reader) -> ChanResult<KLineUnit> {
+    let time = r.time()?;
+    let kl_type = r.kl_type()?;
+    let (open, high, low, close) = (r.f64()?, r.f64()?, r.f64()?, r.f64()?);
+    let mut trade_info = TradeInfo {
+        volume: r.opt_f64()?,
+        turnover: r.opt_f64()?,
+        turnrate: r.opt_f64()?,
+        open_interest: r.opt_f64()?,
+        bid: r.opt_f64()?,
+        ask: r.opt_f64()?,
+        trade_count: r.opt_f64()?,
+        ..TradeInfo::default()
+    };
+    for _ in 0..r.u32()? {
+        let name = r.str()?;
+        trade_info.extra.insert(name, r.f64()?);
+    }
+    // 快照里的KLU都是校验通过（或已修正）的，原样恢复
+    let mut klu = KLineUnit::new(time, open, high, low, close, trade_info, false)
+        .map_err(|_| corrupted("bad klu"))?;
+    klu.kl_type = kl_type;
+    klu.limit_flag = r.i32()?;
+    klu.is_halted = r.bool()?;
+    let has_macd = r.bool()?;
+    let macd = MacdItem {
+        fast_ema: r.f64()?,
+        slow_ema: r.f64()?,
+        dif: r.f64()?,
+        dea: r.f64()?,
+        macd: r.f64()?,
+    };
+    klu.trade_info.macd = has_macd.then_some(macd);
+    Ok(klu)
+}
+
+fn write_klc(w: &mut Writer, klc: &KLine) {
+    w.kl_type(klc.kl_type);
+    w.time(&klc.time_begin());
+    w.time(&klc.time_end());
+    w.f64(klc.high());
+    w.f64(klc.low());
+    let range = klc.klu_range();
+    w.u64(*range.start() as u64);
+    w.u64(*range.end() as u64);
+    w.dir(klc.dir());
+    w.fx_type(klc.fx());
+}
+
+/// `begin` 为这根KLC应当开始的KLU下标，范围不连续或越过 `klu_cnt` 时在分配之前就报错；
+/// KLU 的 `klc` 归属由调用方按KLC的范围回填
+fn read_klc(r: &mut Reader, idx: usize, begin: usize, klu_cnt: usize) -> ChanResult<KLine> {
+    let kl_type = r.kl_type()?;
+    let (time_begin, time_end) = (r.time()?, r.time()?);
+    let (high, low) = (r.f64()?, r.f64()?);
+    let (start, end) = (r.usize()?, r.usize()?);
+    if start != begin || end < begin || end >= klu_cnt {
+        return Err(corrupted("bad klc range"));
+    }
+    let combiner = Combiner::from_parts(
+        time_begin,
+        time_end,
+        high,
+        low,
+        (begin..=end).collect(),
+        r.dir()?,
+        r.fx_type()?,
+    );
+    Ok(KLine::from_parts(idx, kl_type, combiner))
+}
+
+fn write_fx(w: &mut Writer, fx: &Fx) {
+    w.u64(fx.id);
+    w.fx_type(fx.fx_type);
+    w.u64(fx.klc_idx as u64);
+    w.time(&fx.time_begin);
+    w.time(&fx.time_end);
+    w.f64(fx.high);
+    w.f64(fx.low);
+    w.bool(fx.is_sure);
+    w.bool(fx.is_virtual);
+    w.bool(fx.gap.with_pre);
+    w.bool(fx.gap.with_next);
+}
+
+fn read_fx(r: &mut Reader, idx: usize) -> ChanResult<Fx> {
+    Ok(Fx {
+        id: r.u64()?,
+        idx,
+        fx_type: r.fx_type()?,
+        klc_idx: r.usize()?,
+        time_begin: r.time()?,
+        time_end: r.time()?,
+        high: r.f64()?,
+        low: r.f64()?,
+        is_sure: r.bool()?,
+        is_virtual: r.bool()?,
+        gap: FxGap {
+            with_pre: r.bool()?,
+            with_next: r.bool()?,
+        },
+    })
+}
+
+pub(crate) fn encode(
+    kl_type: Option<KLineType>,
+    config: &ChanConfig,
+    state: ListState,
+    klus: &[KLineUnit],
+    lst: &[KLine],
+    fx_list: &FxList,
+) -> ChanSnapshot {
+    let mut w = Writer::default();
+    w.buf.extend_from_slice(MAGIC);
+    w.u16(SNAPSHOT_VERSION);
+    w.kl_type(kl_type);
+    write_config(&mut w, config);
+    w.u64(state.invalid_bar_cnt as u64);
+    w.u64(state.trimmed_klu_cnt as u64);
+    w.bool(state.rebuilt);
+    w.u64(klus.len() as u64);
+    for klu in klus {
+        write_klu(&mut w, klu);
+    }
+    w.u64(lst.len() as u64);
+    for klc in lst {
+        write_klc(&mut w, klc);
+    }
+    w.u64(fx_list.next_id());
+    w.u64(fx_list.len() as u64);
+    for fx in fx_list.iter() {
+        write_fx(&mut w, fx);
+    }
+    let checksum = fnv1a(&w.buf);
+    w.u64(checksum);
+    ChanSnapshot::from_bytes(w.buf)
+}
+
+pub(crate) fn decode(snapshot: &ChanSnapshot) -> ChanResult<SnapshotData> {
+    let bytes = snapshot.as_bytes();
+    if bytes.len() < MAGIC.len() + 2 + 8 || !bytes.starts_with(MAGIC) {
+        return Err(corrupted("bad magic"));
+    }
+    let mut r = Reader {
+        buf: bytes,
+        pos: MAGIC.len(),
+    };
+    let version = r.u16()?;
+    if version != SNAPSHOT_VERSION {
+        return Err(ChanException::new(
+            tr(MsgKey::SnapshotVersion, &[&version, &SNAPSHOT_VERSION]),
+            ErrCode::SnapshotErr,
+        ));
+    }
+    let (body, tail) = bytes.split_at(bytes.len() - 8);
+    let checksum = u64::from_le_bytes(tail.try_into().expect("8 bytes"));
+    if fnv1a(body) != checksum {
+        return Err(corrupted("checksum mismatch"));
+    }
+
+    let mut r = Reader {
+        buf: body,
+        pos: r.pos,
+    };
+    let kl_type = r.kl_type()?;
+    let config = read_config(&mut r)?;
+    let state = ListState {
+        invalid_bar_cnt: r.usize()?,
+        trimmed_klu_cnt: r.usize()?,
+        rebuilt: r.bool()?,
+    };
+    let klu_cnt = r.usize()?;
+    let mut klus = Vec::new();
+    for idx in 0..klu_cnt {
+        let mut klu = read_klu(&mut r)?;
+        klu.idx = idx;
+        klus.push(klu);
+    }
+    let klc_cnt = r.usize()?;
+    let mut lst: Vec<KLine> = Vec::new();
+    for idx in 0..klc_cnt {
+        let begin = lst.last().map_or(0, |klc| klc.klu_range().end() + 1);
+        lst.push(read_klc(&mut r, idx, begin, klu_cnt)?);
+    }
+    let next_id = r.u64()?;
+    let fx_cnt = r.usize()?;
+    let mut fxs = Vec::new();
+    for idx in 0..fx_cnt {
+        fxs.push(read_fx(&mut r, idx)?);
+    }
+    if r.pos != body.len() {
+        return Err(corrupted("trailing bytes"));
+    }
+    if fxs.last().is_some_and(|fx| fx.id >= next_id) {
+        return Err(corrupted("bad fx id"));
+    }
+    for klc in &lst {
+        for klu in &mut klus[klc.klu_range()] {
+            klu.klc = Some(klc.idx);
+        }
+    }
+    Ok(SnapshotData {
+        kl_type,
+        config,
+        state,
+        klus,
+        lst,
+        fx_list: FxList::from_parts(fxs, next_id),
+    })
+}
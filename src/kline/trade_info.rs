@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::common::enums::DataField;
+use crate::math::macd::MacdItem;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TradeInfo {
+    pub volume: Option<f64>,        // 成交量
+    pub turnover: Option<f64>,      // 成交额
+    pub turnrate: Option<f64>,      // 换手率
+    pub open_interest: Option<f64>, // 持仓量，期货用
+    pub bid: Option<f64>,
+    pub ask: Option<f64>,
+    pub trade_count: Option<f64>,    // 成交笔数
+    pub extra: HashMap<String, f64>, // 其他数据源自带的指标
+    pub macd: Option<MacdItem>,      // 入列时由 `KLineList` 计算，不是数据源字段
+}
+
+impl TradeInfo {
+    /// 按字段名构造，不认识的字段放进 `extra`
+    pub fn from_fields<'a>(fields: impl IntoIterator<Item = (&'a str, f64)>) -> Self {
+        let mut info = Self::default();
+        for (name, value) in fields {
+            info.set(name, value);
+        }
+        info
+    }
+
+    pub fn set(&mut self, name: &str, value: f64) {
+        match name {
+            DataField::FIELD_VOLUME => self.volume = Some(value),
+            DataField::FIELD_TURNOVER => self.turnover = Some(value),
+            DataField::FIELD_TURNRATE => self.turnrate = Some(value),
+            DataField::FIELD_OPEN_INTEREST => self.open_interest = Some(value),
+            DataField::FIELD_BID => self.bid = Some(value),
+            DataField::FIELD_ASK => self.ask = Some(value),
+            DataField::FIELD_TRADE_COUNT => self.trade_count = Some(value),
+            _ => {
+                self.extra.insert(name.to_string(), value);
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<f64> {
+        match name {
+            DataField::FIELD_VOLUME => self.volume,
+            DataField::FIELD_TURNOVER => self.turnover,
+            DataField::FIELD_TURNRATE => self.turnrate,
+            DataField::FIELD_OPEN_INTEREST => self.open_interest,
+            DataField::FIELD_BID => self.bid,
+            DataField::FIELD_ASK => self.ask,
+            DataField::FIELD_TRADE_COUNT => self.trade_count,
+            _ => self.extra.get(name).copied(),
+        }
+    }
+
+    /// 导出所有有值的指标，固定字段在前，`extra` 按名字排序
+    pub fn to_fields(&self) -> Vec<(String, f64)> {
+        let fixed = [
+            (DataField::FIELD_VOLUME, self.volume),
+            (DataField::FIELD_TURNOVER, self.turnover),
+            (DataField::FIELD_TURNRATE, self.turnrate),
+            (DataField::FIELD_OPEN_INTEREST, self.open_interest),
+            (DataField::FIELD_BID, self.bid),
+            (DataField::FIELD_ASK, self.ask),
+            (DataField::FIELD_TRADE_COUNT, self.trade_count),
+        ];
+        let mut extra: Vec<(String, f64)> = self
+            .extra
+            .iter()
+            .map(|(name, value)| (name.clone(), *value))
+            .collect();
+        extra.sort_by(|a, b| a.0.cmp(&b.0));
+        fixed
+            .into_iter()
+            .filter_map(|(name, value)| value.map(|v| (name.to_string(), v)))
+            .chain(extra)
+            .collect()
+    }
+}
+
+// chan.py 原有的三个指标，没有值时也打印 None
+const BASE_FIELDS: [&str; 3] = [
+    DataField::FIELD_VOLUME,
+    DataField::FIELD_TURNOVER,
+    DataField::FIELD_TURNRATE,
+];
+
+fn fmt_metric(v: Option<f64>) -> String {
+    v.map_or_else(|| "None".to_string(), |x| x.to_string())
+}
+
+impl fmt::Display for TradeInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "volume:{} turnover:{} turnover_rate:{}",
+            fmt_metric(self.volume),
+            fmt_metric(self.turnover),
+            fmt_metric(self.turnrate)
+        )?;
+        for (name, value) in self.to_fields() {
+            if !BASE_FIELDS.contains(&name.as_str()) {
+                write!(f, " {name}:{value}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fields_roundtrip() {
+        let info = TradeInfo::from_fields([
+            ("volume", 100.0),
+            ("open_interest", 5000.0),
+            ("funding_rate", 0.0001),
+            ("bid", 9.9),
+        ]);
+        assert_eq!(info.volume, Some(100.0));
+        assert_eq!(info.open_interest, Some(5000.0));
+        assert_eq!(info.get("funding_rate"), Some(0.0001));
+        assert_eq!(info.get("ask"), None);
+
+        let names: Vec<String> = info.to_fields().into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, ["volume", "open_interest", "bid", "funding_rate"]);
+        assert_eq!(
+            info.to_string(),
+            "volume:100 turnover:None turnover_rate:None open_interest:5000 bid:9.9 funding_rate:0.0001"
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use crate::common::chan_exception::{ChanException, ChanResult, ErrCode};
+use crate::common::messages::{tr, MsgKey};
+use crate::kline::kline_list::KLineList;
+use crate::kline::kline_unit::KLineUnit;
+
+/// 预热结束后交出的结果
+#[derive(Debug)]
+pub struct WarmUpDone {
+    pub kl_list: KLineList,
+    /// 补缓存时被拒绝的实时K线及原因，不影响其余K线和已预热的历史
+    pub rejected: Vec<(KLineUnit, ChanException)>,
+}
+
+/// 两阶段运行：先在后台线程灌入历史K线，期间到达的实时K线先缓存，
+/// 预热结束后补上缓存再切换为逐根实时计算，预热不阻塞实时循环
+#[derive(Debug)]
+pub struct WarmUp {
+    handle: Option<JoinHandle<ChanResult<KLineList>>>,
+    done: Arc<AtomicUsize>,
+    total: usize,
+    pending: Vec<KLineUnit>,
+}
+
+impl WarmUp {
+    /// `on_progress(已处理根数, 总根数)` 在后台线程里每处理一根调用一次
+    pub fn spawn(
+        mut kl_list: KLineList,
+        history: Vec<KLineUnit>,
+        mut on_progress: impl FnMut(usize, usize) + Send + 'static,
+    ) -> Self {
+        let total = history.len();
+        let done = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&done);
+        let handle = thread::spawn(move || {
+            for (i, klu) in history.into_iter().enumerate() {
+                kl_list.add_single_klu(klu)?;
+                counter.store(i + 1, Ordering::Relaxed);
+                on_progress(i + 1, total);
+            }
+            Ok(kl_list)
+        });
+        Self {
+            handle: Some(handle),
+            done,
+            total,
+            pending: Vec::new(),
+        }
+    }
+
+    /// (已处理根数, 总根数)
+    pub fn progress(&self) -> (usize, usize) {
+        (self.done.load(Ordering::Relaxed), self.total)
+    }
+
+    /// 预热期间到达的实时K线，切换时按顺序补上
+    ///
+    /// 结果交出后再缓存就不会被补上了，此时返回错误，应直接加到交出的 `kl_list` 上
+    pub fn push_live(&mut self, klu: KLineUnit) -> ChanResult<()> {
+        if self.handle.is_none() {
+            return Err(ChanException::new(
+                tr(MsgKey::WarmUpTaken, &[]),
+                ErrCode::CommonError,
+            ));
+        }
+        self.pending.push(klu);
+        Ok(())
+    }
+
+    /// 不阻塞：预热未结束返回 None，结束后补上缓存的实时K线并交出结果，只会交出一次
+    pub fn poll(&mut self) -> ChanResult<Option<WarmUpDone>> {
+        if !self.handle.as_ref().is_some_and(|h| h.is_finished()) {
+            return Ok(None);
+        }
+        self.switch().map(Some)
+    }
+
+    /// 阻塞等待预热结束，补上缓存的实时K线后交出结果
+    pub fn finish(mut self) -> ChanResult<WarmUpDone> {
+        self.switch()
+    }
+
+    /// 只有预热本身失败时返回错误；缓存的实时K线出错时跳过并记入 `rejected`
+    fn switch(&mut self) -> ChanResult<WarmUpDone> {
+        let handle = self.handle.take().ok_or_else(|| {
+            ChanException::new(tr(MsgKey::WarmUpTaken, &[]), ErrCode::CommonError)
+        })?;
+        let mut kl_list = handle.join().map_err(|_| {
+            ChanException::new(tr(MsgKey::WarmUpPanicked, &[]), ErrCode::CommonError)
+        })??;
+        let mut rejected = Vec::new();
+        for klu in self.pending.drain(..) {
+            if let Err(err) = kl_list.add_single_klu(klu.clone()) {
+                rejected.push((klu, err));
+            }
+        }
+        Ok(WarmUpDone { kl_list, rejected })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chan_config::{ChanConfig, InvalidBarPolicy};
+    use crate::common::ctime::CTime;
+    use crate::common::enums::KLineType;
+    use crate::testkit::MarketGen;
+
+    #[test]
+    fn test_warm_up() {
+        let series = MarketGen::new(CTime::new(2024, 1, 1, 0, 0, 0, false), 10.0)
+            .trend(5, 2.0, 1.0, 4)
+            .build()
+            .unwrap();
+        let (history, live) = series.klus.split_at(25);
+
+        let mut expected = KLineList::new(Some(KLineType::KDay));
+        for klu in &series.klus {
+            expected.add_single_klu(klu.clone()).unwrap();
+        }
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        let mut warm_up = WarmUp::spawn(
+            KLineList::new(Some(KLineType::KDay)),
+            history.to_vec(),
+            move |_, _| {
+                counter.fetch_add(1, Ordering::Relaxed);
+            },
+        );
+        for klu in live {
+            warm_up.push_live(klu.clone()).unwrap();
+        }
+        let done = loop {
+            if let Some(done) = warm_up.poll().unwrap() {
+                break done;
+            }
+            thread::yield_now();
+        };
+        assert!(done.rejected.is_empty());
+        let kl_list = done.kl_list;
+        assert_eq!(warm_up.progress(), (25, 25));
+        assert_eq!(calls.load(Ordering::Relaxed), 25);
+        assert_eq!(kl_list.klu_cnt(), series.klus.len());
+        assert_eq!(kl_list.fx_list().len(), expected.fx_list().len());
+        // 只交出一次，之后的实时K线不再缓存
+        assert!(warm_up.poll().unwrap().is_none());
+        let err = warm_up.push_live(live[0].clone()).unwrap_err();
+        assert_eq!(err.errcode, ErrCode::CommonError);
+    }
+
+    #[test]
+    fn test_rejected_live_bar() {
+        let series = MarketGen::new(CTime::new(2024, 1, 1, 0, 0, 0, false), 10.0)
+            .trend(2, 2.0, 1.0, 4)
+            .build()
+            .unwrap();
+        let config = ChanConfig {
+            on_invalid_bar: Some(InvalidBarPolicy::Error),
+            ..Default::default()
+        };
+        let (history, live) = series.klus.split_at(8);
+        let kl_list = KLineList::with_config(Some(KLineType::KDay), config);
+        let mut warm_up = WarmUp::spawn(kl_list, history.to_vec(), |_, _| {});
+        warm_up.push_live(live[0].clone()).unwrap();
+        // 时间倒退的K线被拒绝，后面的照常补上
+        warm_up.push_live(history[0].clone()).unwrap();
+        for klu in &live[1..] {
+            warm_up.push_live(klu.clone()).unwrap();
+        }
+        let done = warm_up.finish().unwrap();
+        assert_eq!(done.kl_list.klu_cnt(), series.klus.len());
+        assert_eq!(done.rejected.len(), 1);
+        assert_eq!(done.rejected[0].0.time, history[0].time);
+        assert_eq!(done.rejected[0].1.errcode, ErrCode::KlNotMonotonous);
+    }
+}
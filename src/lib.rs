@@ -1,3 +1,16 @@
+pub mod batch;
+pub mod bi;
+pub mod chan_config;
+pub mod combiner;
+pub mod common;
+pub mod data_api;
+pub mod engine;
+pub mod ffi;
+pub mod kline;
+pub mod math;
+pub mod multi_level;
+pub mod testkit;
+
 pub fn add(left: u64, right: u64) -> u64 {
     left + right
 }
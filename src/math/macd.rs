@@ -0,0 +1,112 @@
+use crate::common::chan_exception::{ChanException, ChanResult, ErrCode};
+use crate::common::messages::{tr, MsgKey};
+
+/// 单根KLU上的MACD值，同 chan.py 的 CMACD_item
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MacdItem {
+    pub fast_ema: f64,
+    pub slow_ema: f64,
+    pub dif: f64,
+    pub dea: f64,
+    pub macd: f64, // 柱子，2 * (dif - dea)
+}
+
+/// 增量计算MACD，每次喂一个收盘价
+#[derive(Debug, Clone)]
+pub struct MacdCalculator {
+    fast_period: usize,
+    slow_period: usize,
+    signal_period: usize,
+    last: Option<MacdItem>,
+}
+
+fn ema(value: f64, pre: f64, period: usize) -> f64 {
+    (2.0 * value + (period - 1) as f64 * pre) / (period + 1) as f64
+}
+
+impl MacdCalculator {
+    pub fn new(fast_period: usize, slow_period: usize, signal_period: usize) -> ChanResult<Self> {
+        if fast_period == 0 || slow_period == 0 || signal_period == 0 {
+            return Err(ChanException::new(
+                tr(
+                    MsgKey::InvalidMacdPeriod,
+                    &[&fast_period, &slow_period, &signal_period],
+                ),
+                ErrCode::ParaError,
+            ));
+        }
+        Ok(Self {
+            fast_period,
+            slow_period,
+            signal_period,
+            last: None,
+        })
+    }
+
+    /// 第一个值的两条EMA都取该值本身，DIF、DEA为0
+    pub fn add(&mut self, value: f64) -> MacdItem {
+        let item = match self.last {
+            None => MacdItem {
+                fast_ema: value,
+                slow_ema: value,
+                ..MacdItem::default()
+            },
+            Some(pre) => {
+                let fast_ema = ema(value, pre.fast_ema, self.fast_period);
+                let slow_ema = ema(value, pre.slow_ema, self.slow_period);
+                let dif = fast_ema - slow_ema;
+                let dea = ema(dif, pre.dea, self.signal_period);
+                MacdItem {
+                    fast_ema,
+                    slow_ema,
+                    dif,
+                    dea,
+                    macd: 2.0 * (dif - dea),
+                }
+            }
+        };
+        self.last = Some(item);
+        item
+    }
+
+    pub fn last(&self) -> Option<&MacdItem> {
+        self.last.as_ref()
+    }
+
+    /// 从 `last` 之后接着算，None 表示从头开始；回退或重算K线时使用
+    pub(crate) fn reset_to(&mut self, last: Option<MacdItem>) {
+        self.last = last;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_macd() {
+        assert!(MacdCalculator::new(12, 0, 9).is_err());
+
+        let mut calc = MacdCalculator::new(1, 3, 1).unwrap();
+        assert_eq!(calc.add(10.0).dif, 0.0);
+        // 周期为1的EMA就是当前值
+        let item = calc.add(14.0);
+        assert_eq!((item.fast_ema, item.slow_ema), (14.0, 12.0));
+        assert_eq!((item.dif, item.dea, item.macd), (2.0, 2.0, 0.0));
+
+        // 价格不变时柱子收敛到0
+        let mut calc = MacdCalculator::new(12, 26, 9).unwrap();
+        for i in 0..30 {
+            calc.add(10.0 + i as f64);
+        }
+        assert!(calc.last().unwrap().dif > 0.0);
+        for _ in 0..300 {
+            calc.add(40.0);
+        }
+        assert!(calc.last().unwrap().macd.abs() < 1e-6);
+
+        let mut resumed = MacdCalculator::new(12, 26, 9).unwrap();
+        resumed.reset_to(calc.last().copied());
+        assert_eq!(resumed.add(41.0), calc.add(41.0));
+    }
+}
@@ -0,0 +1,190 @@
+//! 多级别联立：同时维护若干级别的 `KLineList`，并把相邻级别的KLU按时间挂接成父子关系，供区间套分析
+
+use std::ops::Index;
+
+use crate::chan_config::ChanConfig;
+use crate::common::chan_exception::{ChanException, ChanResult, ErrCode};
+use crate::common::enums::KLineType;
+use crate::common::func_util::check_kltype_order;
+use crate::common::messages::{tr, MsgKey};
+use crate::kline::kline_list::KLineList;
+use crate::kline::kline_unit::KLineUnit;
+
+#[derive(Debug, Clone)]
+pub struct MultiLevelChan {
+    lv_list: Vec<KLineType>,
+    lists: Vec<KLineList>,
+    max_kl_misalgin_cnt: usize,
+}
+
+impl MultiLevelChan {
+    /// `lv_list` 从大级别到小级别排列，各级别共用同一份配置
+    pub fn new(lv_list: &[KLineType], config: ChanConfig) -> ChanResult<Self> {
+        check_kltype_order(lv_list)?;
+        Ok(Self {
+            lv_list: lv_list.to_vec(),
+            lists: lv_list
+                .iter()
+                .map(|&kl_type| KLineList::with_config(Some(kl_type), config.clone()))
+                .collect(),
+            max_kl_misalgin_cnt: 2,
+        })
+    }
+
+    /// 相邻级别允许对不齐的父KLU根数，默认2，同 chan.py
+    pub fn max_kl_misalgin_cnt(mut self, cnt: usize) -> Self {
+        self.max_kl_misalgin_cnt = cnt;
+        self
+    }
+
+    pub fn lv_list(&self) -> &[KLineType] {
+        &self.lv_list
+    }
+
+    pub fn get(&self, kl_type: KLineType) -> Option<&KLineList> {
+        let lv_idx = self.lv_list.iter().position(|&lv| lv == kl_type)?;
+        Some(&self.lists[lv_idx])
+    }
+
+    /// 向 `kl_type` 级别追加一根KLU，并与上下相邻级别重新挂接；
+    /// 挂接出错时撤销这根KLU和本次的挂接，各级别保持调用前的状态；
+    /// 撤销本身也出错时仍返回挂接的错误，撤销的错误附在消息后面
+    pub fn add_klu(&mut self, kl_type: KLineType, klu: KLineUnit) -> ChanResult<()> {
+        let lv_idx = self
+            .lv_list
+            .iter()
+            .position(|&lv| lv == kl_type)
+            .ok_or_else(|| {
+                ChanException::new(
+                    tr(
+                        MsgKey::KlTypeNotSupported,
+                        &[&format!("{:?}", self.lv_list), &format!("{kl_type:?}")],
+                    ),
+                    ErrCode::ParaError,
+                )
+            })?;
+        // 与上一级、下一级的挂接，以及各自的次级别中已挂接的根数
+        let marks: Vec<(usize, usize)> = (lv_idx.saturating_sub(1)..self.lists.len() - 1)
+            .take_while(|&pair_idx| pair_idx <= lv_idx)
+            .map(|pair_idx| (pair_idx, self.lists[pair_idx + 1].stitched_cnt()))
+            .collect();
+        let klu_cnt = self.lists[lv_idx].klu_cnt();
+        self.lists[lv_idx].add_single_klu(klu)?;
+        for &(pair_idx, _) in &marks {
+            if let Err(mut err) = self.stitch(pair_idx) {
+                if let Err(rollback_err) = self.rollback(lv_idx, klu_cnt, &marks) {
+                    err.msg = tr(MsgKey::RollbackFailed, &[&err.msg, &rollback_err]);
+                }
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    fn rollback(
+        &mut self,
+        lv_idx: usize,
+        klu_cnt: usize,
+        marks: &[(usize, usize)],
+    ) -> ChanResult<()> {
+        for &(pair_idx, sub_begin) in marks {
+            let (parents, subs) = self.lists.split_at_mut(pair_idx + 1);
+            parents[pair_idx].unstitch_sub_level(&mut subs[0], sub_begin);
+        }
+        self.lists[lv_idx].truncate_klus(klu_cnt)
+    }
+
+    /// 挂接第 `lv_idx` 级和下一级
+    fn stitch(&mut self, lv_idx: usize) -> ChanResult<usize> {
+        let (parents, subs) = self.lists.split_at_mut(lv_idx + 1);
+        parents[lv_idx].stitch_sub_level(&mut subs[0], self.max_kl_misalgin_cnt)
+    }
+}
+
+impl Index<usize> for MultiLevelChan {
+    type Output = KLineList;
+
+    fn index(&self, lv_idx: usize) -> &KLineList {
+        &self.lists[lv_idx]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::ctime::CTime;
+    use crate::kline::trade_info::TradeInfo;
+
+    fn klu(hour: u32, minute: u32) -> KLineUnit {
+        day_klu(2, hour, minute)
+    }
+
+    fn day_klu(day: u32, hour: u32, minute: u32) -> KLineUnit {
+        let t = CTime::new(2024, 1, day, hour, minute, 0, hour == 0);
+        KLineUnit::new(t, 10.0, 11.0, 9.0, 10.5, TradeInfo::default(), false).unwrap()
+    }
+
+    #[test]
+    fn test_multi_level() {
+        let lv_list = [KLineType::K60M, KLineType::K30M, KLineType::K5M];
+        let mut chan = MultiLevelChan::new(&lv_list, ChanConfig::default()).unwrap();
+        // 实盘中小级别先到
+        for m in [35, 40, 45, 50, 55] {
+            chan.add_klu(KLineType::K5M, klu(9, m)).unwrap();
+        }
+        chan.add_klu(KLineType::K5M, klu(10, 0)).unwrap();
+        chan.add_klu(KLineType::K30M, klu(10, 0)).unwrap();
+        chan.add_klu(KLineType::K60M, klu(10, 30)).unwrap();
+        chan.add_klu(KLineType::K30M, klu(10, 30)).unwrap();
+
+        assert_eq!(chan[1].klu(0).unwrap().sub_kl_list, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(chan[0].klu(0).unwrap().sub_kl_list, vec![0, 1]);
+        assert_eq!(
+            chan.get(KLineType::K30M).unwrap().klu(1).unwrap().sup_kl,
+            Some(0)
+        );
+
+        let err = chan.add_klu(KLineType::KDay, klu(11, 0)).unwrap_err();
+        assert_eq!(err.errcode, ErrCode::ParaError);
+        let err = MultiLevelChan::new(&[KLineType::K5M, KLineType::K30M], ChanConfig::default())
+            .unwrap_err();
+        assert_eq!(err.errcode, ErrCode::ParaError);
+    }
+
+    #[test]
+    fn test_day_parent() {
+        let lv_list = [KLineType::KDay, KLineType::K60M];
+        let mut chan = MultiLevelChan::new(&lv_list, ChanConfig::default()).unwrap();
+        for (h, m) in [(10, 30), (11, 30), (14, 0), (15, 0)] {
+            chan.add_klu(KLineType::K60M, day_klu(2, h, m)).unwrap();
+        }
+        chan.add_klu(KLineType::KDay, day_klu(2, 0, 0)).unwrap();
+        // 下一天的60分钟K线不能挂到前一天
+        chan.add_klu(KLineType::K60M, day_klu(3, 10, 30)).unwrap();
+        assert_eq!(chan[0].klu(0).unwrap().sub_kl_list, vec![0, 1, 2, 3]);
+        assert_eq!(chan[1].klu(4).unwrap().sup_kl, None);
+        chan.add_klu(KLineType::KDay, day_klu(3, 0, 0)).unwrap();
+        assert_eq!(chan[0].klu(1).unwrap().sub_kl_list, vec![4]);
+    }
+
+    #[test]
+    fn test_add_klu_rollback() {
+        let lv_list = [KLineType::KDay, KLineType::K60M];
+        let mut chan = MultiLevelChan::new(&lv_list, ChanConfig::default())
+            .unwrap()
+            .max_kl_misalgin_cnt(1);
+        chan.add_klu(KLineType::K60M, day_klu(2, 10, 30)).unwrap();
+        for day in 2..=4 {
+            chan.add_klu(KLineType::KDay, day_klu(day, 0, 0)).unwrap();
+        }
+        // 1月3日没有60分钟K线，1月4日的挂上去之后父级别出现对不齐
+        let err = chan
+            .add_klu(KLineType::K60M, day_klu(4, 10, 30))
+            .unwrap_err();
+        assert_eq!(err.errcode, ErrCode::KlDataNotAlign);
+        assert_eq!(chan[1].klu_cnt(), 1);
+        assert!(chan[0].klu(2).unwrap().sub_kl_list.is_empty());
+        assert_eq!(chan[0].klu(0).unwrap().sub_kl_list, vec![0]);
+        assert_eq!(chan[1].klu(0).unwrap().sup_kl, Some(0));
+    }
+}
@@ -0,0 +1,214 @@
+use crate::common::chan_exception::ChanResult;
+use crate::common::ctime::CTime;
+use crate::common::enums::FxType;
+use crate::kline::kline_unit::KLineUnit;
+use crate::kline::trade_info::TradeInfo;
+
+/// 生成序列时已知的转折点，即相邻两段反向走势交界处的最高（最低）KLU
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TurningPoint {
+    pub fx_type: FxType,
+    pub klu_idx: usize,
+    pub time: CTime,
+    pub price: f64,
+}
+
+/// `MarketGen::build` 的结果
+#[derive(Debug, Clone)]
+pub struct SyntheticSeries {
+    pub klus: Vec<KLineUnit>,
+    pub turning_points: Vec<TurningPoint>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    Leg { bars: usize, to: f64 },
+    Gap(f64),
+}
+
+/// 确定性的合成K线生成器，由若干段直线走势拼成，用于测试和演示
+///
+/// 段内每根KLU首尾相接、高低点同向移动，不会出现包含；每段第一根开盘价向本段方向让出
+/// 四分之一步，保证转折处的最高（最低）KLU唯一
+#[derive(Debug, Clone)]
+pub struct MarketGen {
+    start: CTime,
+    step_secs: i64,
+    start_price: f64,
+    price: f64,
+    steps: Vec<Step>,
+}
+
+impl MarketGen {
+    pub fn new(start: CTime, start_price: f64) -> Self {
+        Self {
+            start,
+            step_secs: 86400,
+            start_price,
+            price: start_price,
+            steps: Vec::new(),
+        }
+    }
+
+    /// 相邻KLU的时间间隔，默认一天
+    pub fn step_secs(mut self, step_secs: i64) -> Self {
+        self.step_secs = step_secs;
+        self
+    }
+
+    /// 用 `bars` 根KLU直线走到 `to`，`bars` 为 0 或价格不变时忽略
+    pub fn leg(mut self, bars: usize, to: f64) -> Self {
+        if bars > 0 && to != self.price {
+            self.steps.push(Step::Leg { bars, to });
+            self.price = to;
+        }
+        self
+    }
+
+    /// 下一根KLU从当前价格加 `delta` 处开始
+    pub fn gap(mut self, delta: f64) -> Self {
+        self.steps.push(Step::Gap(delta));
+        self.price += delta;
+        self
+    }
+
+    /// `waves` 浪带回调的趋势：每浪先用 `bars` 根走 `advance`（负数为下跌），
+    /// 再用一半根数反向回调 `pullback`
+    pub fn trend(mut self, waves: usize, advance: f64, pullback: f64, bars: usize) -> Self {
+        for _ in 0..waves {
+            let top = self.price + advance;
+            self = self
+                .leg(bars, top)
+                .leg((bars / 2).max(1), top - pullback * advance.signum());
+        }
+        self
+    }
+
+    /// 在 [low, high] 之间来回 `cycles` 次的震荡
+    pub fn range(mut self, cycles: usize, low: f64, high: f64, bars: usize) -> Self {
+        for _ in 0..cycles {
+            self = self.leg(bars, high).leg(bars, low);
+        }
+        self
+    }
+
+    /// 先跌 `depth` 再涨回原价的V形反转，`depth` 为负数时是倒V
+    pub fn v_reversal(self, depth: f64, bars: usize) -> Self {
+        let price = self.price;
+        self.leg(bars, price - depth).leg(bars, price)
+    }
+
+    pub fn build(&self) -> ChanResult<SyntheticSeries> {
+        let mut klus = Vec::new();
+        let mut leg_begins: Vec<(usize, bool)> = Vec::new(); // (第一根KLU下标, 是否向上)
+        let mut price = self.start_price;
+        let mut ts = self.start.ts();
+        for step in &self.steps {
+            let (bars, to) = match *step {
+                Step::Gap(delta) => {
+                    price += delta;
+                    continue;
+                }
+                Step::Leg { bars, to } => (bars, to),
+            };
+            let d = (to - price) / bars as f64;
+            leg_begins.push((klus.len(), d > 0.0));
+            for i in 0..bars {
+                let p = price + d * i as f64;
+                let open = if i == 0 { p + d / 4.0 } else { p };
+                let close = if i + 1 == bars { to } else { p + d };
+                let trade_info = TradeInfo {
+                    volume: Some(100.0),
+                    ..TradeInfo::default()
+                };
+                klus.push(KLineUnit::new(
+                    CTime::from_ts(ts),
+                    open,
+                    open.max(close),
+                    open.min(close),
+                    close,
+                    trade_info,
+                    false,
+                )?);
+                ts += self.step_secs;
+            }
+            price = to;
+        }
+
+        let mut turning_points = Vec::new();
+        for w in leg_begins.windows(2) {
+            let ((_, pre_up), (begin, up)) = (w[0], w[1]);
+            if pre_up == up {
+                continue;
+            }
+            // 有跳空时极值可能落在新一段的第一根上
+            let (pre, cur) = (&klus[begin - 1], &klus[begin]);
+            let (fx_type, klu_idx, price) = if pre_up {
+                let idx = if cur.high > pre.high {
+                    begin
+                } else {
+                    begin - 1
+                };
+                (FxType::Top, idx, klus[idx].high)
+            } else {
+                let idx = if cur.low < pre.low { begin } else { begin - 1 };
+                (FxType::Bottom, idx, klus[idx].low)
+            };
+            turning_points.push(TurningPoint {
+                fx_type,
+                klu_idx,
+                time: klus[klu_idx].time,
+                price,
+            });
+        }
+        Ok(SyntheticSeries {
+            klus,
+            turning_points,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::enums::KLineType;
+    use crate::kline::kline_list::KLineList;
+
+    #[test]
+    fn test_fx_match_turning_points() {
+        let series = MarketGen::new(CTime::new(2024, 1, 1, 0, 0, 0, false), 10.0)
+            .trend(3, 2.0, 1.0, 4)
+            .v_reversal(3.0, 4)
+            .gap(-0.5)
+            .range(2, 11.0, 13.0, 3)
+            .trend(2, -2.0, 1.0, 4)
+            .build()
+            .unwrap();
+        assert_eq!(series.turning_points.len(), 12);
+
+        let mut kl_list = KLineList::new(Some(KLineType::KDay));
+        for klu in &series.klus {
+            kl_list.add_single_klu(klu.clone()).unwrap();
+        }
+        let found: Vec<(FxType, CTime)> = kl_list
+            .fx_list()
+            .iter()
+            .map(|fx| (fx.fx_type, kl_list.fx_peak_klu(fx).unwrap().time))
+            .collect();
+        let expected: Vec<(FxType, CTime)> = series
+            .turning_points
+            .iter()
+            .map(|tp| (tp.fx_type, tp.time))
+            .collect();
+        assert_eq!(found, expected);
+        assert!(kl_list.check_invariants().is_empty());
+
+        // 每浪 4 根上涨加 2 根回调，最后停在 10 + 3 * (2 - 1)
+        let again = MarketGen::new(CTime::new(2024, 1, 1, 0, 0, 0, false), 10.0)
+            .trend(3, 2.0, 1.0, 4)
+            .build()
+            .unwrap();
+        assert_eq!(again.klus.len(), 18);
+        assert_eq!(again.klus[17].close, 13.0);
+    }
+}